@@ -1,17 +1,24 @@
 use crossterm::{
     cursor::*,
+    event::{DisableMouseCapture, EnableMouseCapture},
     terminal::{self, *},
 };
 
-pub struct AltScreen;
+pub struct AltScreen {
+    mouse: bool,
+}
 
 impl AltScreen {
-    pub fn enter() -> anyhow::Result<Self> {
+    /// Enters the alternate screen, capturing the mouse if `mouse` is set
+    pub fn enter(mouse: bool) -> anyhow::Result<Self> {
         let mut stdout = std::io::stdout();
         crossterm::execute!(stdout, EnterAlternateScreen)?;
         terminal::enable_raw_mode()?;
-        crossterm::execute!(stdout, Clear(ClearType::All), Hide)?;
-        Ok(Self {})
+        crossterm::execute!(stdout, Clear(ClearType::All), MoveTo(0, 0), Hide)?;
+        if mouse {
+            crossterm::execute!(stdout, EnableMouseCapture)?;
+        }
+        Ok(Self { mouse })
     }
 }
 
@@ -19,6 +26,9 @@ impl Drop for AltScreen {
     fn drop(&mut self) {
         let _ = terminal::disable_raw_mode();
         let mut out = std::io::stdout();
+        if self.mouse {
+            let _ = crossterm::execute!(out, DisableMouseCapture);
+        }
         let _ = crossterm::execute!(out, LeaveAlternateScreen, Show);
     }
 }
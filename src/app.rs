@@ -1,96 +1,364 @@
-use std::{net::TcpStream, time::Duration};
+use std::{
+    net::TcpStream,
+    time::{Duration, Instant},
+};
 
 use crate::{
-    args::Args,
+    args::{Args, Command},
+    clipboard,
+    clock::Clock,
+    filter::Filter,
+    helix,
     keys::{self, Message},
-    twitch,
+    moderate::{self, Action, Room},
+    overlay::{Overlay, Whisper},
+    prompt::{Prompt, PromptKind},
+    queue::Queue,
+    stats::Stats,
+    terminal::Terminal,
+    throttle::Throttle,
+    timestamp, transcript,
+    twitch::{self, Notice},
     window::{UpdateMode, ViewMode, Window},
     Logger,
 };
 
 use crossterm::event::*;
 use flume as channel;
+use twitchchat::messages::Privmsg;
+
+// how many messages a notch of the mouse wheel scrolls by
+const WHEEL_LINES: usize = 3;
+// how many whispers are kept
+const WHISPERS_MAX: usize = 100;
+// how often relative timestamps are brought up to date, when nothing else redraws them
+const RELATIVE_TICK: Duration = Duration::from_secs(1);
+
+/// What picking a mark does
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Marking {
+    Delete,
+    /// marks are picked until enter is pressed
+    DeleteMany,
+    /// everything from the marked message's user
+    DeleteUser,
+    Copy,
+    /// a moderation action, chosen with tab
+    Moderate,
+    /// shows the reply thread of the marked message
+    Thread,
+    /// shows what has been seen of the marked message's author
+    UserInfo,
+    /// hides everything from the marked message's user, now and later
+    Mute,
+}
+
+impl Marking {
+    /// The next way of deleting, for cycling through them
+    const fn next(self) -> Self {
+        match self {
+            Self::Delete => Self::DeleteMany,
+            Self::DeleteMany => Self::DeleteUser,
+            Self::DeleteUser => Self::Delete,
+            Self::Copy => Self::Copy,
+            Self::Moderate => Self::Moderate,
+            Self::Thread => Self::Thread,
+            Self::UserInfo => Self::UserInfo,
+            Self::Mute => Self::Mute,
+        }
+    }
+}
 
 pub struct App {
     pub(crate) view_mode: ViewMode,
     pub(crate) waiting: bool,
+    pub(crate) marking: Marking,
+    pub(crate) prompt: Option<Prompt>,
     pub(crate) window: Option<Window>,
     pub(crate) args: Args,
+    pub(crate) terminal: Box<dyn Terminal>,
+    pub(crate) events: (channel::Sender<Message>, channel::Receiver<Message>),
+    /// where messages typed into the compose prompt go, only set when authenticated
+    pub(crate) outgoing: Option<channel::Sender<String>>,
+    /// where confirmed moderation actions go, also only set when authenticated
+    pub(crate) moderation: Option<channel::Sender<moderate::Pending>>,
+    // twitch says we can moderate the channel
+    moderator: bool,
+    pub(crate) notices: Option<channel::Receiver<Notice>>,
+    /// the last thing the server told us, until escape is pressed
+    pub(crate) notice: Option<String>,
+    pub(crate) room: Room,
+    pub(crate) action: Action,
+    /// a moderation action waiting for a y/n
+    pub(crate) confirming: Option<moderate::Pending>,
+    pub(crate) whispers: Queue<Whisper>,
+    /// whispers that came in since the list was last looked at
+    pub(crate) unread: usize,
+    pub(crate) overlay: Option<Overlay>,
+    pub(crate) stats: Stats,
+    /// holds back messages when they come in faster than they can be read
+    pub(crate) throttle: Option<Throttle>,
+    /// where the throttle and the relative timestamps get the time from
+    pub(crate) clock: Clock,
+    /// when relative timestamps were last brought up to date
+    pub(crate) ticked: Instant,
 }
 
 impl App {
     pub fn run(args: Args, mut logger: Logger) -> anyhow::Result<()> {
         logger.transcribe(&format!("*** session start: {}", crate::timestamp()))?;
 
-        let conn = if args.debug {
-            use crate::testing::*;
-            TcpStream::connect(make_interesting_chat(TestingOpts::load())?)?
-        } else {
-            TcpStream::connect(twitchchat::TWITCH_IRC_ADDRESS)?
-        };
+        let mut window = Window::new(args.nick_max, args.buffer_max, args.min_width)
+            .with_collapse(args.collapse)
+            .with_panes(&args.panes);
+        let mut stats = Stats::default();
 
-        let (sender, messages) = channel::bounded(64);
-        let _ = std::thread::spawn({
-            let channel = args.channel.clone();
-            move || {
-                let _ = twitch::run_to_completion(channel, sender, conn);
+        // when viewing a transcript there is no connection, so nothing will be fed to us
+        let (messages, outgoing, moderation, notices) = match &args.command {
+            Command::Chat => {
+                let conn = Self::connect(&args)?;
+                let outgoing = Some(conn.outgoing);
+                (
+                    Some(conn.messages),
+                    outgoing,
+                    conn.moderation,
+                    Some(conn.notices),
+                )
             }
-        });
-        let (events_tx, events_rx) = channel::bounded(32);
-
-        let mut this = Self {
-            view_mode: args
-                .min_width
-                .map(|_| ViewMode::Compact)
-                .unwrap_or(ViewMode::Normal),
-            window: Some(Window::new(args.nick_max, args.buffer_max, args.min_width)),
-            waiting: false,
-            args,
+            Command::View(path) => {
+                for entry in transcript::load(path, &args.channel)? {
+                    stats.record(&entry.pm, entry.ts);
+                    window.push_at(entry.pm, entry.ts);
+                }
+                (None, None, None, None)
+            }
+            // this is done without the ui
+            Command::Export(..) => unreachable!("exporting doesn't run the app"),
         };
 
-        'outer: while keep_running(&messages) {
-            if crossterm::event::poll(Duration::from_millis(150))? {
-                match crossterm::event::read()? {
-                    Event::Key(event) => keys::handle(event, &events_tx),
-                    Event::Resize(_, _) => {
-                        this.update(UpdateMode::Redraw)?;
+        let mut this = Self::new(args, window, Box::new(std::io::stdout()));
+        this.outgoing = outgoing.filter(|_| this.args.auth.is_some());
+        this.moderation = moderation;
+        this.notices = notices;
+        this.stats = stats;
+
+        // it was printed before the screen was taken over, this keeps it in view
+        if let Some(seed) = this.args.seed.filter(|_| this.args.debug_script.is_none()) {
+            this.notice = Some(format!("seed: {}", seed));
+        }
+
+        if messages.is_none() {
+            this.update(UpdateMode::Redraw)?;
+        }
+
+        while this.step(messages.as_ref(), &mut logger)? {}
+
+        this.stats.transcribe(&mut logger)
+    }
+
+    /// Handles any input, and then any new messages
+    ///
+    /// This returns false once the app should stop
+    pub(crate) fn step(
+        &mut self,
+        messages: Option<&channel::Receiver<Privmsg<'static>>>,
+        logger: &mut Logger,
+    ) -> anyhow::Result<bool> {
+        if !messages.map_or(true, keep_running) {
+            return Ok(false);
+        }
+
+        match self.terminal.poll(Duration::from_millis(150))? {
+            Some(Event::Key(event)) => keys::handle(event, self.key_mode(), &self.events.0),
+            Some(Event::Mouse(event)) => keys::handle_mouse(event, self.key_mode(), &self.events.0),
+            Some(Event::Resize(_, _)) => self.update(UpdateMode::Redraw)?,
+            _ => {}
+        }
+
+        let events = self.events.1.clone();
+        for event in events.try_iter() {
+            if !self.dispatch(event, logger)? {
+                return Ok(false);
+            }
+        }
+
+        let notices = self.notices.clone();
+        for notice in notices.iter().flat_map(|notices| notices.try_iter()) {
+            match notice {
+                Notice::Text(text) => self.notice = Some(text),
+                // whatever was drawn before this is gone
+                Notice::Joined => {
+                    if let Some(window) = &mut self.window {
+                        window.invalidate();
+                    }
+                }
+                Notice::Room(id) => self.room.id = Some(id),
+                Notice::Moderator(moderator) => self.moderator = moderator,
+                Notice::Slow(slow) => self.room.slow = slow,
+                Notice::SubsOnly(subs_only) => self.room.subs_only = subs_only,
+                Notice::Moderated { login, what } => {
+                    if let Some(window) = &mut self.window {
+                        window
+                            .users_mut()
+                            .moderated(&login, chrono::Local::now(), what)
                     }
-                    _ => {}
                 }
+                Notice::Whisper { from, text } => {
+                    let ts = chrono::Local::now();
+                    self.whispers.push(Whisper { ts, from, text });
+                    if self.overlay != Some(Overlay::Whispers) {
+                        self.unread += 1;
+                    }
+                }
+            }
+            match self.overlay {
+                Some(..) => self.update(UpdateMode::Redraw)?,
+                None => self.update(UpdateMode::Status)?,
             }
+        }
+
+        if self.waiting {
+            return Ok(true);
+        }
 
-            for event in events_rx.try_iter() {
-                if !this.dispatch(event)? {
-                    break 'outer;
+        let mut held = false;
+        for msg in messages
+            .into_iter()
+            .flat_map(|messages| messages.try_iter())
+        {
+            let received = chrono::Local::now();
+            let ts = timestamp::sent_at(&msg).unwrap_or(received);
+            logger.transcribe(&self.args.transcript_format.line(ts.timestamp() as _, &msg))?;
+            self.stats.record(&msg, ts);
+
+            match &mut self.throttle {
+                Some(throttle) => {
+                    throttle.push(msg, received);
+                    held = true;
                 }
+                None => self.show(msg, received)?,
             }
+        }
 
-            if this.waiting {
-                continue 'outer;
-            }
+        let released = match &mut self.throttle {
+            Some(throttle) => throttle.release(self.clock.now()),
+            None => vec![],
+        };
+        // the backlog counter changed, even if nothing can be shown yet
+        if held && released.is_empty() {
+            self.update(UpdateMode::Status)?;
+        }
+        for (msg, received) in released {
+            self.show(msg, received)?;
+        }
 
-            for msg in messages.try_iter() {
-                logger.transcribe(&format!(
-                    "{} {}: {}",
-                    crate::timestamp(),
-                    msg.name(),
-                    msg.data()
-                ))?;
+        // "2m ago" would stay that way in a quiet channel
+        if self.shows_relative_timestamps() && self.clock.elapsed(self.ticked) >= RELATIVE_TICK {
+            self.ticked = self.clock.now();
+            self.update(UpdateMode::Redraw)?;
+        }
 
-                this.update_with_window(
-                    move |window| {
-                        window.push(msg);
-                        Ok(())
-                    },
-                    UpdateMode::Append,
-                )?;
-            }
+        Ok(true)
+    }
+
+    const fn shows_relative_timestamps(&self) -> bool {
+        self.args.timestamps
+            && matches!(self.args.timestamp.style, timestamp::Style::Relative)
+            && matches!(self.view_mode, ViewMode::Normal)
+    }
+
+    fn show(
+        &mut self,
+        msg: Privmsg<'static>,
+        received: chrono::DateTime<chrono::Local>,
+    ) -> anyhow::Result<()> {
+        // the stats are redrawn as they change
+        let update = match self.overlay {
+            Some(Overlay::Stats) => UpdateMode::Redraw,
+            _ => UpdateMode::Append,
+        };
+        self.update_with_window(
+            move |window| {
+                window.push_received(msg, received);
+                Ok(())
+            },
+            update,
+        )
+    }
+
+    pub(crate) fn new(args: Args, window: Window, terminal: Box<dyn Terminal>) -> Self {
+        let throttle = args
+            .throttle
+            .map(|rate| Throttle::new(rate, args.backlog_max, args.sample, Instant::now()));
+        Self {
+            view_mode: args
+                .min_width
+                .map(|_| ViewMode::Compact)
+                .unwrap_or(ViewMode::Normal),
+            window: Some(window),
+            waiting: false,
+            marking: Marking::Delete,
+            prompt: None,
+            args,
+            terminal,
+            events: channel::bounded(32),
+            outgoing: None,
+            moderation: None,
+            moderator: false,
+            notices: None,
+            notice: None,
+            room: Room::default(),
+            action: Action::Delete,
+            confirming: None,
+            whispers: Queue::with_size(WHISPERS_MAX),
+            unread: 0,
+            overlay: None,
+            stats: Stats::default(),
+            throttle,
+            clock: Clock::default(),
+            ticked: Instant::now(),
         }
+    }
+
+    fn connect(args: &Args) -> anyhow::Result<twitch::Connection> {
+        let (addr, urls) = if let Some(script) = &args.debug_script {
+            use crate::testing::*;
+            let addr = make_scripted_chat(Script::load(script)?)?;
+            (addr.to_string(), make_fake_api()?)
+        } else if args.debug {
+            use crate::testing::*;
+            let mut opts = TestingOpts::load();
+            if let Some(seed) = args.seed {
+                opts.seed = seed;
+            }
+            let addr = make_interesting_chat(opts)?;
+            (addr.to_string(), make_fake_api()?)
+        } else {
+            let addr = twitchchat::TWITCH_IRC_ADDRESS.to_string();
+            (addr, helix::Urls::default())
+        };
+
+        let conn = TcpStream::connect(&*addr)?;
+        Ok(twitch::spawn(
+            args.channel.clone(),
+            args.auth.clone(),
+            urls,
+            addr,
+            conn,
+            std::io::stdout(),
+        ))
+    }
 
-        todo!();
+    const fn key_mode(&self) -> keys::Mode {
+        match (&self.prompt, self.waiting) {
+            (Some(..), ..) => keys::Mode::Input,
+            _ if self.confirming.is_some() => keys::Mode::Input,
+            (None, true) => keys::Mode::Marking,
+            (None, false) => keys::Mode::Normal,
+        }
     }
 
-    fn dispatch(&mut self, event: Message) -> anyhow::Result<bool> {
+    fn dispatch(&mut self, event: Message, logger: &mut Logger) -> anyhow::Result<bool> {
         use {Message as M, ViewMode as V};
 
         let update_mode = match self.waiting {
@@ -101,28 +369,217 @@ impl App {
         match (event, self.view_mode) {
             (M::Quit, ..) => return Ok(false),
 
-            (M::Redraw, ..) => self.update(UpdateMode::Redraw)?,
+            (M::Redraw, ..) => {
+                if let Some(window) = &mut self.window {
+                    window.invalidate();
+                }
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::Cancel, ..) if self.overlay.is_some() => {
+                self.overlay.take();
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::Filter, ..) if self.overlay.as_ref().is_some_and(Overlay::is_popup) => {
+                if let Some(Overlay::User { login, .. }) = self.overlay.take() {
+                    self.scroll(|window| {
+                        window.set_filter(Some(Filter::Name(login)));
+                        true
+                    })?;
+                }
+            }
+
+            (M::ScrollUp | M::ScrollDown, ..) if self.overlay.is_some() => {
+                let scrolled = match (&mut self.overlay, event) {
+                    (Some(overlay), M::ScrollUp) => overlay.scroll_up(),
+                    (Some(overlay), ..) => overlay.scroll_down(),
+                    (None, ..) => false,
+                };
+                if scrolled {
+                    self.update(UpdateMode::Redraw)?;
+                }
+            }
+            // the chat is underneath, so nothing else makes sense
+            _ if self.overlay.is_some() => {}
+
+            (M::Whispers, ..) if self.outgoing.is_some() => {
+                self.overlay = Some(Overlay::Whispers);
+                self.unread = 0;
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::Stats, ..) => {
+                self.overlay = Some(Overlay::Stats);
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::UserInfo, view_mode) if !self.waiting => {
+                let selected = self.window.as_ref().and_then(Window::selected);
+                match selected {
+                    Some(id) => self.show_user(id)?,
+                    // the marks are only available in the normal view
+                    None if view_mode == V::Normal => {
+                        self.waiting = true;
+                        self.marking = Marking::UserInfo;
+                        self.update(UpdateMode::MarkAll)?;
+                    }
+                    None => {}
+                }
+            }
+
+            (M::Mute, view_mode) if !self.waiting => {
+                let selected = self.window.as_ref().and_then(Window::selected);
+                match selected {
+                    Some(id) => self.delete(logger, "muted", |w| w.mute(id))?,
+                    // the marks are only available in the normal view
+                    None if view_mode == V::Normal => {
+                        self.waiting = true;
+                        self.marking = Marking::Mute;
+                        self.update(UpdateMode::MarkAll)?;
+                    }
+                    None => {}
+                }
+            }
+
+            (M::Thread, view_mode) if !self.waiting => {
+                let selected = self.window.as_ref().and_then(Window::selected);
+                match selected {
+                    Some(id) => self.scroll(|window| window.expand_thread(id))?,
+                    // the marks are only available in the normal view
+                    None if view_mode == V::Normal => {
+                        self.waiting = true;
+                        self.marking = Marking::Thread;
+                        self.update(UpdateMode::MarkAll)?;
+                    }
+                    None => {}
+                }
+            }
 
             (M::Delete, V::Normal) if !self.waiting => {
                 self.waiting = !self.waiting;
+                self.marking = Marking::Delete;
                 self.update(UpdateMode::MarkAll)?;
             }
 
-            (M::Delete, V::Normal) if self.waiting => {
-                self.waiting = !self.waiting;
-                self.update(UpdateMode::Redraw)?
+            (M::Delete, V::Normal) if self.waiting => self.stop_marking()?,
+
+            (M::Copy, view_mode) if !self.waiting => {
+                let format = self.args.copy_format;
+                match self.window.as_ref().and_then(|w| w.copy_selected(format)) {
+                    Some(text) => clipboard::copy(&mut self.terminal, &text)?,
+                    // the marks are only available in the normal view
+                    None if view_mode == V::Normal => {
+                        self.waiting = true;
+                        self.marking = Marking::Copy;
+                        self.update(UpdateMode::MarkAll)?;
+                    }
+                    None => {}
+                }
             }
 
             (M::Char(ch), V::Normal) if self.waiting => {
-                return self
-                    .with_window(|window, this| {
-                        window.delete(ch, this)?;
-                        this.waiting = false;
-                        Ok(())
-                    })
-                    .map(|_| true)
+                let id = self.window.as_mut().and_then(|w| w.type_mark(ch));
+                match (id, self.marking) {
+                    // show what has been typed so far
+                    (None, ..) => self.update(UpdateMode::MarkAll)?,
+                    (Some(id), Marking::Delete) => {
+                        self.delete(logger, "deleted", |w| w.delete(id))?
+                    }
+                    (Some(id), Marking::DeleteUser) => {
+                        self.delete(logger, "deleted", |w| w.delete_user(id))?
+                    }
+                    (Some(id), Marking::DeleteMany) => {
+                        self.update_with_window(
+                            |window| {
+                                window.pick(id);
+                                Ok(())
+                            },
+                            UpdateMode::MarkAll,
+                        )?;
+                    }
+                    (Some(id), Marking::Copy) => {
+                        let format = self.args.copy_format;
+                        let text = self.window.as_mut().and_then(|w| w.copy(id, format));
+                        self.copy_and_stop_marking(text)?;
+                    }
+                    (Some(id), Marking::UserInfo) => self.show_user(id)?,
+                    (Some(id), Marking::Mute) => self.delete(logger, "muted", |w| w.mute(id))?,
+                    (Some(id), Marking::Thread) => {
+                        self.waiting = false;
+                        self.update_with_window(
+                            |window| {
+                                window.expand_thread(id);
+                                Ok(())
+                            },
+                            UpdateMode::Redraw,
+                        )?;
+                    }
+                    (Some(id), Marking::Moderate) => {
+                        let target = self.window.as_mut().and_then(|w| w.target(id));
+                        self.confirm(self.action.pending(target.as_ref(), self.room))?;
+                    }
+                }
+            }
+
+            // twitch would only refuse it once it's been asked
+            (M::Moderate, V::Normal)
+                if !self.waiting && self.moderation.is_some() && !self.moderator =>
+            {
+                self.notice = Some("you aren't a moderator here".into());
+                self.update(UpdateMode::Status)?;
+            }
+
+            (M::Moderate, V::Normal) if !self.waiting && self.moderation.is_some() => {
+                self.waiting = true;
+                self.marking = Marking::Moderate;
+                self.update(UpdateMode::MarkAll)?;
+            }
+
+            (M::Submit, V::Normal)
+                if self.waiting
+                    && self.marking == Marking::Moderate
+                    && self.action.is_channel_wide() =>
+            {
+                self.confirm(self.action.pending(None, self.room))?
+            }
+
+            (M::Input(ch), ..) if self.confirming.is_some() => {
+                let pending = self.confirming.take();
+                if let (Some(pending), Some(moderation), 'y') = (pending, &self.moderation, ch) {
+                    let _ = moderation.send(pending);
+                }
+                self.update(UpdateMode::Redraw)?;
             }
 
+            (M::Submit | M::Cancel, ..) if self.confirming.is_some() => {
+                self.confirming.take();
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::Submit, V::Normal) if self.waiting && self.marking == Marking::DeleteMany => {
+                self.delete(logger, "deleted", Window::delete_picked)?
+            }
+
+            (M::CopyShown, V::Normal) if self.waiting && self.marking == Marking::Copy => {
+                let format = self.args.copy_format;
+                let text = self.window.as_mut().and_then(|w| w.copy_marked(format));
+                self.copy_and_stop_marking(text)?;
+            }
+
+            (M::CycleMarking, ..) if self.waiting => {
+                match self.marking {
+                    Marking::Copy => self.args.copy_format = self.args.copy_format.next(),
+                    Marking::Moderate => self.action = self.action.next(),
+                    marking => self.marking = marking.next(),
+                }
+                self.update(UpdateMode::MarkAll)?;
+            }
+
+            (M::Undo, ..) if !self.waiting => self.delete(logger, "restored", Window::undo)?,
+
+            (M::CopyLast, ..) => self.open_prompt(PromptKind::CopyLast)?,
+
             (M::NameColumnGrow, V::Normal) => self.with_window(|window, this| {
                 if Window::grow_nick_column(window) {
                     return window.update(this, update_mode);
@@ -137,17 +594,193 @@ impl App {
                 Ok(())
             })?,
 
-            (M::ToggleTimestamps, V::Compact) => {
+            (M::ToggleTimestamps, ..) => {
                 self.args.timestamps = !self.args.timestamps;
+                self.update(update_mode)?;
+            }
+
+            (M::ToggleContinuation, view_mode) => {
+                let continuation = self.args.continuation_mut(view_mode);
+                *continuation = continuation.next();
+                self.update(UpdateMode::Redraw)?;
+            }
+
+            (M::ScrollUp, ..) => self.scroll(|window| window.scroll_up(1))?,
+            (M::ScrollDown, ..) => self.scroll(|window| window.scroll_down(1))?,
+            (M::PageUp, ..) => self.scroll(|window| window.scroll_up(window.page_size()))?,
+            (M::PageDown, ..) => self.scroll(|window| window.scroll_down(window.page_size()))?,
+            (M::ScrollTop, ..) => self.scroll(|window| window.scroll_up(usize::MAX))?,
+            (M::ScrollBottom, ..) => self.scroll(|window| window.scroll_down(usize::MAX))?,
+            (M::WheelUp, ..) => self.scroll(|window| window.scroll_up(WHEEL_LINES))?,
+            (M::WheelDown, ..) => self.scroll(|window| window.scroll_down(WHEEL_LINES))?,
+
+            (M::Click(column, row), ..) => self.scroll(|window| window.click(column, row))?,
+            (M::DeleteSelected, ..) => self.delete(logger, "deleted", Window::delete_selected)?,
+
+            (M::SearchOlder, ..) => self.scroll(Window::search_older)?,
+            (M::SearchNewer, ..) => self.scroll(Window::search_newer)?,
+
+            (M::Search, ..) => self.open_prompt(PromptKind::Search)?,
+            (M::Filter, ..) => self.open_prompt(PromptKind::Filter)?,
+            (M::Compose, ..) if self.outgoing.is_some() => self.open_prompt(PromptKind::Compose)?,
+
+            (M::Input(ch), ..) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.input.push(ch);
+                    self.update(UpdateMode::Status)?;
+                }
+            }
+
+            (M::Backspace, ..) => {
+                if let Some(prompt) = &mut self.prompt {
+                    prompt.input.pop();
+                    self.update(UpdateMode::Status)?;
+                }
+            }
+
+            (M::Submit, ..) => {
+                if let Some(prompt) = self.prompt.take() {
+                    let format = self.args.copy_format;
+                    let outgoing = self.outgoing.clone();
+                    let mut copied = None;
+                    self.update_with_window(
+                        |window| {
+                            match prompt.kind {
+                                PromptKind::Search => window.search(&prompt.input),
+                                PromptKind::Filter => {
+                                    window.set_filter(Filter::parse(&prompt.input))
+                                }
+                                PromptKind::CopyLast => {
+                                    copied = (prompt.input.trim().parse().ok())
+                                        .and_then(|n| window.copy_last(n, format))
+                                }
+                                // it shows up once twitch has accepted it
+                                PromptKind::Compose => {
+                                    let text = prompt.input.trim();
+                                    if let (false, Some(outgoing)) = (text.is_empty(), &outgoing) {
+                                        let _ = outgoing.send(text.to_string());
+                                    }
+                                }
+                            }
+                            Ok(())
+                        },
+                        UpdateMode::Redraw,
+                    )?;
+                    if let Some(text) = copied {
+                        clipboard::copy(&mut self.terminal, &text)?;
+                    }
+                }
+            }
+
+            (M::Cancel, ..) if self.waiting => self.stop_marking()?,
+
+            (M::Cancel, ..) if self.prompt.is_some() => {
+                self.prompt.take();
                 self.update(UpdateMode::Redraw)?;
             }
 
+            (M::Cancel, ..) => {
+                if self.notice.take().is_some() {
+                    self.update(UpdateMode::Status)?;
+                }
+                self.scroll(Window::reset)?
+            }
+
             _ => {}
         }
 
         Ok(true)
     }
 
+    fn copy_and_stop_marking(&mut self, text: Option<String>) -> anyhow::Result<()> {
+        if let Some(text) = text {
+            clipboard::copy(&mut self.terminal, &text)?;
+        }
+        self.stop_marking()
+    }
+
+    fn stop_marking(&mut self) -> anyhow::Result<()> {
+        self.waiting = false;
+        self.update_with_window(
+            |window| {
+                window.clear_marks();
+                Ok(())
+            },
+            UpdateMode::Redraw,
+        )
+    }
+
+    // hides (or restores) messages, noting them in the transcript when asked to
+    fn delete(
+        &mut self,
+        logger: &mut Logger,
+        note: &str,
+        func: impl FnOnce(&mut Window) -> Vec<String>,
+    ) -> anyhow::Result<()> {
+        let mut lines = vec![];
+        self.waiting = false;
+        self.update_with_window(
+            |window| {
+                lines = func(window);
+                window.clear_marks();
+                Ok(())
+            },
+            UpdateMode::Redraw,
+        )?;
+
+        if self.args.note_deletions {
+            for line in lines {
+                logger.transcribe(&format!("*** {} locally: {}", note, line))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn show_user(&mut self, id: usize) -> anyhow::Result<()> {
+        self.waiting = false;
+        let target = self.window.as_mut().and_then(|w| w.target(id));
+        if let Some(target) = target {
+            self.overlay = Some(Overlay::User {
+                login: target.login,
+                scroll: 0,
+            });
+        }
+        self.update(UpdateMode::Redraw)
+    }
+
+    // asks before doing anything, or says why it can't be done
+    fn confirm(&mut self, pending: anyhow::Result<moderate::Pending>) -> anyhow::Result<()> {
+        self.waiting = false;
+        self.confirming = match pending {
+            Ok(pending) => Some(pending),
+            Err(err) => {
+                self.notice = Some(err.to_string());
+                None
+            }
+        };
+        self.update_with_window(
+            |window| {
+                window.clear_marks();
+                Ok(())
+            },
+            UpdateMode::Redraw,
+        )
+    }
+
+    fn open_prompt(&mut self, kind: PromptKind) -> anyhow::Result<()> {
+        self.prompt.replace(Prompt::new(kind));
+        self.update(UpdateMode::Redraw)
+    }
+
+    fn scroll(&mut self, func: impl FnOnce(&mut Window) -> bool) -> anyhow::Result<()> {
+        self.with_window(|window, this| {
+            if func(window) {
+                return window.update(this, UpdateMode::Redraw);
+            }
+            Ok(())
+        })
+    }
+
     #[track_caller]
     fn with_window(
         &mut self,
@@ -184,6 +817,964 @@ impl App {
     }
 }
 
+// keep going until the connection is gone and its messages have been shown
 fn keep_running<T>(ch: &channel::Receiver<T>) -> bool {
-    !matches!(ch.try_recv(), Err(channel::TryRecvError::Disconnected))
+    !ch.is_disconnected() || !ch.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::Harness;
+
+    const SCRIPT: &str = "
+        0 msg museun hello world
+        0 msg shaken_bot !hello
+        0 msg someone hi there
+    ";
+
+    const NORMAL: &[&str] = &[
+        "",
+        "museun      hello world",
+        "shaken_bot  !hello",
+        "someone     hi there",
+    ];
+
+    fn key(ch: char, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(ch), modifiers)
+    }
+
+    fn assert_screen(harness: &Harness, expected: &[&str]) {
+        assert_eq!(harness.screen(), expected.join("\n"));
+    }
+
+    #[test]
+    fn delete() {
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        assert_screen(&harness, NORMAL);
+
+        harness.press(key('d', KeyModifiers::CONTROL));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "[C] museun  hello world",
+                "[B] shaken… !hello",
+                "[A] someone hi there",
+            ],
+        );
+
+        harness.press(key('B', KeyModifiers::SHIFT));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "someone     hi there",
+                "",
+                "",
+                "1 hidden",
+            ],
+        );
+
+        // it was received, so it stays in the transcript
+        assert_eq!(
+            harness.transcript(),
+            [
+                "museun: hello world",
+                "shaken_bot: !hello",
+                "someone: hi there"
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_many() {
+        let args = Args {
+            note_deletions: true,
+            ..Args::default()
+        };
+        let script = format!("{}\n0 msg museun bye", SCRIPT);
+        let mut harness = Harness::new(args, (40, 7), &script);
+        harness.wait_for_messages(4);
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+
+        // pick a couple of messages, then delete them together
+        harness.press(key('d', KeyModifiers::CONTROL));
+        harness.press(tab);
+        harness.press(key('A', KeyModifiers::SHIFT));
+        harness.press(key('C', KeyModifiers::SHIFT));
+        assert_eq!(
+            harness.screen().lines().last(),
+            Some("delete 2: pick marks, enter to delete")
+        );
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "someone     hi there",
+                "",
+                "",
+                "",
+                "2 hidden",
+            ],
+        );
+
+        // everything from a user
+        harness.press(key('d', KeyModifiers::CONTROL));
+        harness.press(tab);
+        harness.press(tab);
+        harness.press(key('B', KeyModifiers::SHIFT));
+        assert_screen(
+            &harness,
+            &["", "someone     hi there", "", "", "", "", "3 hidden"],
+        );
+
+        // each deletion is undone on its own
+        harness.press(key('u', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "someone     hi there",
+                "",
+                "",
+                "",
+                "2 hidden",
+            ],
+        );
+        harness.press(key('u', KeyModifiers::NONE));
+        assert_eq!(harness.screen().lines().count(), 5);
+
+        assert_eq!(
+            harness.transcript()[4..],
+            [
+                "deleted locally: shaken_bot: !hello",
+                "deleted locally: museun: bye",
+                "deleted locally: museun: hello world",
+                "restored locally: museun: hello world",
+                "restored locally: shaken_bot: !hello",
+                "restored locally: museun: bye",
+            ]
+        );
+    }
+
+    #[test]
+    fn delete_two_character_marks() {
+        let script = "
+            0 repeat 70 msg museun hello
+            0 msg shaken_bot bye
+        ";
+        let mut harness = Harness::new(Args::default(), (40, 80), script);
+        harness.wait_for_messages(71);
+
+        harness.press(key('d', KeyModifiers::CONTROL));
+        let lines = harness
+            .screen()
+            .lines()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(lines[1], "[BI] museun hello");
+        assert_eq!(lines[71], "[AA] shake… bye");
+
+        // nothing happens until the whole mark is typed
+        harness.press(key('A', KeyModifiers::SHIFT));
+        assert!(harness.screen().contains("bye"));
+        harness.press(key('A', KeyModifiers::SHIFT));
+        assert!(!harness.screen().contains("bye"));
+        assert!(harness.screen().ends_with("1 hidden"));
+    }
+
+    #[test]
+    fn delete_cancel() {
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+
+        harness.press(key('d', KeyModifiers::CONTROL));
+        harness.press(key('d', KeyModifiers::CONTROL));
+        assert_screen(&harness, NORMAL);
+    }
+
+    #[test]
+    fn name_column() {
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+
+        harness.press(key(']', KeyModifiers::NONE));
+        harness.press(key(']', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun        hello world",
+                "shaken_bot    !hello",
+                "someone       hi there",
+            ],
+        );
+
+        for _ in 0..4 {
+            harness.send(Message::NameColumnShrink);
+        }
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun    hello world",
+                "shaken_b… !hello",
+                "someone   hi there",
+            ],
+        );
+    }
+
+    #[test]
+    fn view_mode() {
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+
+        let compact = &[
+            "",
+            "museun",
+            "hello world",
+            "",
+            "shaken_bot",
+            "!hello",
+            "",
+            "someone",
+            "hi there",
+        ];
+
+        harness.resize(20, 10);
+        assert_screen(&harness, compact);
+
+        // deletion is only available in the normal view
+        harness.press(key('d', KeyModifiers::CONTROL));
+        assert_screen(&harness, compact);
+
+        harness.resize(40, 6);
+        assert_screen(&harness, NORMAL);
+    }
+
+    #[test]
+    fn shutdown() {
+        let script = format!("{}\n0 disconnect", SCRIPT);
+        let mut harness = Harness::new(Args::default(), (40, 6), &script);
+        harness.wait_for_shutdown();
+
+        // everything that was received is shown before stopping
+        assert_eq!(harness.transcript().len(), 3);
+        assert_screen(&harness, NORMAL);
+    }
+
+    #[test]
+    fn reconnect() {
+        let script = "
+            0 msg museun hello world
+            0 reconnect
+            0 msg shaken_bot !hello
+        ";
+        let mut harness = Harness::new(Args::default(), (40, 6), script);
+
+        // the script carries on where it left off on the new connection
+        harness.wait_for_messages(2);
+        assert_eq!(
+            harness.transcript(),
+            ["museun: hello world", "shaken_bot: !hello"]
+        );
+    }
+
+    #[test]
+    fn server_events() {
+        for (event, expected) in [
+            ("sub museun 3 still here", "museun subscribed for 3 months!"),
+            ("raid museun 42", "42 raiders from museun have joined!"),
+            ("clearchat", "the chat was cleared"),
+            (
+                "notice msg_channel_suspended no such channel",
+                "no such channel",
+            ),
+        ] {
+            let script = format!("{}\n0 {}", SCRIPT, event);
+            let mut harness = Harness::new(Args::default(), (60, 6), &script);
+            harness.wait_for_messages(3);
+            harness.wait_for(|h| h.screen().ends_with(expected));
+
+            // they're shown on the status line, not in the chat
+            assert_eq!(harness.transcript().len(), 3);
+        }
+    }
+
+    #[test]
+    fn quit() {
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        assert!(harness.is_running());
+
+        harness.press(key('c', KeyModifiers::CONTROL));
+        assert!(!harness.is_running());
+    }
+
+    #[test]
+    fn continuation() {
+        let script = "0 msg museun this message is long enough to be wrapped";
+        let mut harness = Harness::new(Args::default(), (36, 6), script);
+        harness.wait_for_messages(1);
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      this message is long",
+                "            enough to be wrapped",
+            ],
+        );
+
+        harness.press(key('w', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      this message is long",
+                "            ↳ enough to be wrapped",
+            ],
+        );
+
+        harness.press(key('w', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      this message is long",
+                "enough to be wrapped",
+            ],
+        );
+
+        // each view mode has its own style
+        harness.resize(20, 6);
+        assert_screen(
+            &harness,
+            &["", "museun", "this message is long", "enough to be wrapped"],
+        );
+    }
+
+    #[test]
+    fn mouse() {
+        let click = MouseEventKind::Down(MouseButton::Left);
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+
+        // clicking a name filters by it
+        harness.mouse(click, 3, 2);
+        assert_screen(
+            &harness,
+            &["", "shaken_bot  !hello", "", "", "", "filter: @shaken_bot"],
+        );
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_screen(&harness, NORMAL);
+
+        // clicking anywhere else selects the message, so it can be deleted.
+        // this includes the padding after the name
+        harness.mouse(click, 8, 1);
+        harness.press(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "shaken_bot  !hello",
+                "someone     hi there",
+                "",
+                "",
+                "1 hidden",
+            ],
+        );
+
+        // clicking the blank row at the top doesn't select anything
+        harness.mouse(click, 15, 0);
+        harness.press(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "shaken_bot  !hello",
+                "someone     hi there",
+                "",
+                "",
+                "1 hidden",
+            ],
+        );
+
+        harness.mouse(MouseEventKind::ScrollUp, 0, 0);
+        assert_screen(
+            &harness,
+            &["", "shaken_bot  !hello", "", "", "", "1 newer | 1 hidden"],
+        );
+        harness.mouse(MouseEventKind::ScrollDown, 0, 0);
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "shaken_bot  !hello",
+                "someone     hi there",
+                "",
+                "",
+                "1 hidden",
+            ],
+        );
+    }
+
+    #[test]
+    fn mute() {
+        let click = MouseEventKind::Down(MouseButton::Left);
+        let script = format!("{}\n1000 msg shaken_bot again", SCRIPT);
+        let mut harness = Harness::new(Args::default(), (40, 6), &script);
+        harness.wait_for_messages(3);
+
+        // what they send later is hidden too
+        harness.mouse(click, 15, 2);
+        harness.press(key('x', KeyModifiers::NONE));
+        harness.wait_for(|h| h.transcript().len() == 4);
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "someone     hi there",
+                "",
+                "",
+                "2 hidden",
+            ],
+        );
+
+        // and undoing it unmutes them
+        harness.press(key('u', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "shaken_bot  !hello",
+                "someone     hi there",
+                "shaken_bot  again",
+            ],
+        );
+
+        // without a selection, a mark is picked
+        harness.press(key('x', KeyModifiers::NONE));
+        assert!(harness.screen().ends_with("mute: press a mark"));
+        harness.press(key('D', KeyModifiers::SHIFT));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "shaken_bot  !hello",
+                "someone     hi there",
+                "shaken_bot  again",
+                "",
+                "1 hidden",
+            ],
+        );
+    }
+
+    #[test]
+    fn copy() {
+        let mut harness = Harness::new(Args::default(), (60, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        let copied = |harness: &Harness| harness.clipboard().map(|s| s.into_bytes());
+        let base64 = |s: &str| Some(clipboard::base64(s.as_bytes()).into_bytes());
+
+        harness.press(key('y', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "[C] museun  hello world",
+                "[B] shaken… !hello",
+                "[A] someone hi there",
+                "",
+                "copy text: press a mark, * for all, tab to change the format",
+            ],
+        );
+
+        harness.press(KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE));
+        harness.press(key('B', KeyModifiers::SHIFT));
+        assert_eq!(copied(&harness), base64("shaken_bot: !hello"));
+        assert_screen(&harness, NORMAL);
+
+        harness.press(key('y', KeyModifiers::NONE));
+        harness.press(key('*', KeyModifiers::NONE));
+        assert_eq!(
+            copied(&harness),
+            base64("museun: hello world\nshaken_bot: !hello\nsomeone: hi there")
+        );
+
+        harness.press(key('Y', KeyModifiers::SHIFT));
+        harness.press(key('2', KeyModifiers::NONE));
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(
+            copied(&harness),
+            base64("shaken_bot: !hello\nsomeone: hi there")
+        );
+
+        // a selected message is copied straight away
+        let click = MouseEventKind::Down(MouseButton::Left);
+        harness.mouse(click, 15, 1);
+        harness.press(key('y', KeyModifiers::NONE));
+        assert_eq!(copied(&harness), base64("museun: hello world"));
+    }
+
+    #[test]
+    fn compose() {
+        let args = Args {
+            auth: Some(crate::auth::Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", "a".repeat(30)),
+            }),
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+
+        let enter = KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE);
+        harness.press(enter);
+        for ch in "hi chat".chars() {
+            harness.press(key(ch, KeyModifiers::NONE));
+        }
+        assert!(harness.screen().ends_with("say: hi chat_"));
+
+        // it's only shown once the server accepted it
+        harness.press(enter).wait_for_messages(4);
+        assert_eq!(harness.transcript()[3], "museun: hi chat");
+        assert!(harness.screen().contains("museun      hi chat"));
+
+        // a refused message isn't mistaken for the next one
+        let say = |harness: &mut Harness, text: &str| {
+            harness.press(enter);
+            for ch in text.chars() {
+                harness.press(key(ch, KeyModifiers::NONE));
+            }
+            harness.press(enter);
+        };
+        say(&mut harness, "hi chat");
+        harness.wait_for(|h| h.screen().contains("Your message is identical"));
+        say(&mut harness, "bye");
+        harness.wait_for_messages(5);
+        assert_eq!(harness.transcript()[4], "museun: bye");
+
+        // it has the id the server gave it, like any other message
+        let args = Args {
+            auth: Some(crate::auth::Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", "a".repeat(30)),
+            }),
+            transcript_format: transcript::Format::Irc,
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        say(&mut harness, "hi chat");
+        harness.wait_for_messages(4);
+        assert!(harness.transcript()[3].contains(";id=sent-"));
+
+        // without a token, there's nothing to compose
+        let mut harness = Harness::new(Args::default(), (40, 6), SCRIPT);
+        harness.wait_for_messages(3).press(enter);
+        assert!(!harness.screen().contains("say:"));
+    }
+
+    #[test]
+    fn moderate() {
+        let args = Args {
+            auth: Some(crate::auth::Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", "a".repeat(30)),
+            }),
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (60, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        let tab = KeyEvent::new(KeyCode::Tab, KeyModifiers::NONE);
+        let status = |harness: &Harness| harness.screen().lines().last().unwrap().to_string();
+
+        harness.press(key('m', KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "[C] museun  hello world",
+                "[B] shaken… !hello",
+                "[A] someone hi there",
+                "",
+                "delete: press a mark, tab to change",
+            ],
+        );
+
+        for _ in 0..4 {
+            harness.press(tab);
+        }
+        assert_eq!(
+            status(&harness).trim_end(),
+            "ban: press a mark, tab to change"
+        );
+        harness.press(key('B', KeyModifiers::SHIFT));
+        assert_eq!(status(&harness).trim_end(), "ban shaken_bot? y/n");
+
+        // it goes through the api, and how it went is shown until escape is pressed
+        harness.press(key('y', KeyModifiers::NONE));
+        harness.wait_for(|h| h.screen().contains("banned shaken_bot"));
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_screen(&harness, NORMAL);
+
+        // the channel's modes don't need a mark
+        harness.press(key('m', KeyModifiers::NONE));
+        for _ in 0..2 {
+            harness.press(tab);
+        }
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_eq!(status(&harness).trim_end(), "turn on slow mode (30s)? y/n");
+        harness.press(key('n', KeyModifiers::NONE));
+        assert_screen(&harness, NORMAL);
+
+        // it says why something can't be done, rather than doing nothing
+        harness.press(key('m', KeyModifiers::NONE));
+        for _ in 0..2 {
+            harness.press(tab);
+        }
+        assert_eq!(
+            status(&harness).trim_end(),
+            "delete: press a mark, tab to change"
+        );
+        harness.press(key('C', KeyModifiers::SHIFT));
+        assert_eq!(
+            status(&harness).trim_end(),
+            "that message can't be deleted, it has no id"
+        );
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_screen(&harness, NORMAL);
+
+        // twitch doesn't take them as chat commands anymore
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        for ch in "/ban shaken_bot".chars() {
+            harness.press(key(ch, KeyModifiers::NONE));
+        }
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        harness.wait_for(|h| h.screen().contains("Unrecognized command: /ban"));
+    }
+
+    #[test]
+    fn not_a_moderator() {
+        let script = "
+            0 raw @badges=;mod=0 :tmi.twitch.tv USERSTATE #testing
+            0 msg museun hello world
+        ";
+        let args = Args {
+            auth: Some(crate::auth::Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", "a".repeat(30)),
+            }),
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), script);
+        harness.wait_for_messages(1);
+
+        // nothing is marked, as twitch would refuse it anyway
+        harness.press(key('m', KeyModifiers::NONE));
+        let status = harness.screen().lines().last().unwrap().to_string();
+        assert_eq!(status.trim_end(), "you aren't a moderator here");
+        assert!(!harness.screen().contains("[A]"));
+    }
+
+    #[test]
+    fn thread() {
+        let script = r"
+            0 msg @id=1 museun hello world
+            0 msg shaken_bot unrelated
+            0 msg @id=3;reply-parent-msg-id=1;reply-parent-display-name=museun;reply-parent-msg-body=hello\sworld someone yes
+        ";
+        let mut harness = Harness::new(Args::default(), (40, 7), script);
+        harness.wait_for_messages(3);
+
+        // the context line takes up a row of its own
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "shaken_bot  unrelated",
+                "            ↪ replying to @museun: hell…",
+                "someone     yes",
+            ],
+        );
+
+        harness.press(key('r', KeyModifiers::NONE));
+        assert!(harness.screen().ends_with("thread: press a mark"));
+        harness.press(key('A', KeyModifiers::SHIFT));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "            ↪ replying to @museun: hell…",
+                "someone     yes",
+                "",
+                "",
+                "filter: thread",
+            ],
+        );
+    }
+
+    #[test]
+    fn whispers() {
+        let args = Args {
+            auth: Some(crate::auth::Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", "a".repeat(30)),
+            }),
+            ..Args::default()
+        };
+        let script = "
+            0 msg museun hello world
+            0 whisper shaken_bot psst
+        ";
+        let mut harness = Harness::new(args, (40, 6), script);
+        harness.wait_for(|h| h.screen().contains("1 whisper"));
+
+        // they're kept out of the chat
+        harness.press(key('W', KeyModifiers::SHIFT));
+        let screen = harness.screen();
+        let lines = screen.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "whispers");
+        assert!(lines[1].ends_with(" shaken_bot: psst"), "{}", lines[1]);
+        assert!(screen.ends_with("whispers: esc to close"));
+
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert_screen(&harness, &["", "museun      hello world"]);
+        assert_eq!(harness.transcript(), vec!["museun: hello world"]);
+    }
+
+    #[test]
+    fn stats() {
+        let script = "
+            0 msg @first-msg=1 museun hello world
+            0 msg shaken_bot hello
+            300 msg museun hello again
+        ";
+        let mut harness = Harness::new(Args::default(), (70, 24), script);
+        harness.wait_for_messages(2);
+
+        harness.press(key('s', KeyModifiers::NONE));
+        assert!(harness.screen().starts_with("stats\n"));
+        assert!(harness.screen().ends_with("stats: esc to close"));
+
+        // they're kept up to date while they're shown
+        harness.wait_for(|h| h.screen().contains("messages: 3"));
+        let screen = harness.screen();
+        for expected in [
+            "chatters: 2",
+            "first-time chatters: 1 (50%)",
+            "museun 2",
+            "hello 3",
+        ] {
+            assert!(screen.contains(expected), "{}\n{}", expected, screen);
+        }
+
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(!harness.screen().contains("chatters"));
+    }
+
+    #[test]
+    fn user_info() {
+        let script = "
+            0 msg museun hello world
+            0 msg shaken_bot !hello
+            0 msg @badges=moderator/1;color=#FF0000 museun second
+            0 clearchat shaken_bot
+        ";
+        let mut harness = Harness::new(Args::default(), (60, 16), script);
+        harness.wait_for_messages(3);
+
+        harness.press(key('i', KeyModifiers::NONE));
+        assert!(harness.screen().ends_with("user info: press a mark"));
+        harness.press(key('A', KeyModifiers::SHIFT));
+
+        let screen = harness.screen();
+        for expected in [
+            "┌─ @museun ─",
+            "│ color: #FF0000",
+            "│ badges: moderator/1",
+            "│ messages: 2",
+            " second ",
+            " hello world ",
+        ] {
+            assert!(screen.contains(expected), "{}\n{}", expected, screen);
+        }
+        assert!(screen.ends_with("f to filter by them, esc to close"));
+
+        // the chat can be filtered by them from here
+        harness.press(key('f', KeyModifiers::NONE));
+        assert!(harness.screen().ends_with("filter: @museun"));
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+
+        // what happened to them is kept, even when their messages aren't shown
+        harness.press(key('i', KeyModifiers::NONE));
+        harness.press(key('B', KeyModifiers::SHIFT));
+        harness.wait_for(|h| h.screen().contains(" banned "));
+        harness.press(KeyEvent::new(KeyCode::Esc, KeyModifiers::NONE));
+        assert!(!harness.screen().contains('┌'));
+    }
+
+    #[test]
+    fn collapse() {
+        let script = "
+            0 msg museun hello world
+            0 msg museun PogChamp
+            0 msg shaken_bot pogchamp
+            0 msg someone PogChamp
+        ";
+        let args = Args {
+            collapse: Some(2),
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), script);
+        harness.wait_for_messages(4);
+
+        // the repeats update the first one, rather than being appended
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      hello world",
+                "            ×3 museun, shaken_bot, some…",
+                "museun      PogChamp",
+            ],
+        );
+        // but they're all in the transcript
+        assert_eq!(harness.transcript().len(), 4);
+    }
+
+    #[test]
+    fn throttle() {
+        let args = Args {
+            throttle: Some(1),
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), SCRIPT);
+        harness.wait_for_messages(3);
+        harness.wait_for(|h| h.screen().ends_with("2 behind"));
+        assert!(!harness.screen().contains("shaken_bot"));
+
+        // they're let through a second apart
+        harness.advance(Duration::from_millis(500));
+        assert!(harness.screen().ends_with("2 behind"));
+        harness.advance(Duration::from_millis(500));
+        assert!(harness.screen().ends_with("1 behind"));
+        harness.advance(Duration::from_secs(1));
+        assert!(harness.screen().contains("someone"));
+        assert_screen(&harness, NORMAL);
+    }
+
+    #[test]
+    fn panes() {
+        let script = "
+            0 msg museun hello world
+            0 msg @badges=moderator/1 shaken_bot !hello
+            0 msg someone hi there
+        ";
+        let args = Args {
+            panes: vec!["right,45%,mods,nick=10".parse().unwrap()],
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (70, 6), script);
+        harness.wait_for_messages(3);
+        assert_screen(
+            &harness,
+            &[
+                "                                      │mods",
+                "museun      hello world               │",
+                "shaken_bot  !hello                    │shaken_bot !hello",
+                "someone     hi there                  │",
+                "                                      │",
+                "                                      │",
+            ],
+        );
+        let screen = harness.screen();
+
+        // the keys and clicks only act on the chat
+        let click = MouseEventKind::Down(MouseButton::Left);
+        harness.mouse(click, 41, 2);
+        harness.press(KeyEvent::new(KeyCode::Delete, KeyModifiers::NONE));
+        assert_eq!(harness.screen(), screen);
+
+        harness.mouse(click, 3, 1);
+        assert_screen(
+            &harness,
+            &[
+                "                                      │mods",
+                "museun      hello world               │",
+                "                                      │shaken_bot !hello",
+                "                                      │",
+                "                                      │",
+                "filter: @museun",
+            ],
+        );
+    }
+
+    #[test]
+    fn user_history() {
+        let script = "
+            0 msg museun first
+            0 msg museun second
+            0 msg shaken_bot !hello
+            0 msg someone hi there
+        ";
+        let args = Args {
+            buffer_max: 2,
+            ..Args::default()
+        };
+        let mut harness = Harness::new(args, (40, 6), script);
+        harness.wait_for_messages(4);
+
+        // their messages are kept after they've left the buffer
+        harness.press(key('f', KeyModifiers::NONE));
+        for ch in "@museun".chars() {
+            harness.press(key(ch, KeyModifiers::NONE));
+        }
+        harness.press(KeyEvent::new(KeyCode::Enter, KeyModifiers::NONE));
+        assert_screen(
+            &harness,
+            &[
+                "",
+                "museun      first",
+                "museun      second",
+                "",
+                "",
+                "filter: @museun",
+            ],
+        );
+
+        // and they can still be deleted from there
+        harness.press(key('d', KeyModifiers::CONTROL));
+        harness.press(key('A', KeyModifiers::SHIFT));
+        assert_screen(
+            &harness,
+            &["", "museun      first", "", "", "", "filter: @museun"],
+        );
+    }
+
+    #[test]
+    fn sent_ts() {
+        let script = "
+            0 msg @tmi-sent-ts=1617628455123 museun hello
+            0 msg shaken_bot world
+        ";
+        let mut harness = Harness::new(Args::default(), (40, 6), script);
+        harness.wait_for_messages(2);
+
+        // the server's time is used for the transcript, when there is one
+        let transcript = harness.raw_transcript();
+        assert_eq!(transcript[0], "1617628455 museun: hello");
+        let (ts, _) = transcript[1].split_once(' ').unwrap();
+        assert!(ts.parse::<i64>().unwrap() > 1617628455);
+    }
 }
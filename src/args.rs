@@ -1,5 +1,19 @@
+use std::{ffi::OsString, path::PathBuf};
+
 use twitchchat::commands::Channel;
 
+use crate::{
+    auth::Auth,
+    clipboard, export,
+    pane::PaneSpec,
+    throttle::Sample,
+    timestamp::{Timestamp, Zone},
+    transcript::Format,
+    truncate::{Align, Ellipsis},
+    window::{Continuation, ViewMode},
+    wrap::Whitespace,
+};
+
 const HEADER: &str = concat!("readchat ", env!("CARGO_PKG_VERSION"));
 
 const HELP_MESSAGE: &str = "
@@ -8,32 +22,154 @@ description:
 
 usage:
     readchat <channel>
+    readchat view <transcript>
+    readchat export <transcript> [--format html|md|csv] [--user <name>]
 
 flags:
     -h, --help             prints this message
     -v, --version          prints the version
     -d, --debug            use simulated debug stream instead
     -t, --transcribe       log this channel to a file (when not in debug mode)
+    -r, --raw              transcribe raw irc lines (keeps colors and badges)
     -l, --log-dir          print the log directory and exit
 
 optional flags:
     -n, --nick-max <int>   the max width before truncation of nicknames
+        --nick-align <align>
+                           align nicknames in their column: left, right or center
+        --nick-ellipsis <where>
+                           where long nicknames are cut: tail or middle
+        --nick-separator <glyph>
+                           draw this between the nicknames and the messages, e.g. │
     -b, --buffer-max <int> the number of messages to keep in the redraw queue
     -m, --min-width <int>  if window size is below this, use a compact view
-    -s, --timestamp        render timestamps of messages, in the compact view
+        --collapse <int>   collapse a message repeated this many times in a row into one line
+        --throttle <int>   show at most this many messages a second, the rest wait in a backlog
+        --backlog-max <int>
+                           how long the backlog gets before new messages are sampled (default: 200)
+        --sample <how>     what is kept once the backlog is full: every nth message (e.g. 3), or
+                           priority: messages from the broadcaster, moderators and vips, and
+                           highlighted messages. without this everything is kept
+        --pane <spec>      split a pane off the chat, showing only what its filter lets through,
+                           e.g. right,40%,mods or below,@museun,nick=8,compact. can be repeated
+    -s, --timestamp        render timestamps of messages
+        --timestamp-format <fmt>
+                           a strftime format, or 'relative' for e.g. 2m ago (default: %X)
+        --utc              render timestamps in utc rather than the local time
+        --latency          render how long each message took to arrive
+        --preserve-spaces  keep runs of spaces in messages instead of collapsing them
+        --continuation <style>
+                           where wrapped lines continue: align, hang or full (default: align)
+        --compact-continuation <style>
+                           the same, but for the compact view (default: align)
+        --copy-format <fmt>
+                           what is copied from a message: text, line or raw (default: text)
+        --note-deletions   note deleted (and restored) messages in the transcript
+        --nick <name>      the nick to chat as, this needs a token too
+        --token <token>    an oauth token for sending messages. it can also be set with
+                           READCHAT_TOKEN, or in the config file (see below)
+        --mouse            use the mouse to scroll, select messages and filter by names
+        --links            make urls clickable, in terminals that support hyperlinks
+        --seed <int>       seed the debug stream, to replay a previous session
+        --debug-script <file>
+                           play back a scripted scenario, implies --debug
+        --format <fmt>     the format to export to: html, md or csv (default: md)
+        --user <name>      only export the messages from this user
 
 arguments:
     <string>               the twitch channel to join
+    <transcript>           a transcript to open instead, e.g. from the log directory
+
+chatting:
+    readchat is read-only until it has a token. the token and nick are taken from the
+    flags, then READCHAT_TOKEN and READCHAT_NICK, then the config file. it is in the
+    config directory, e.g. ~/.config/museun/readchat/config, and looks like:
+        nick = your_name
+        token = your_token
+    press enter to write a message, m to moderate, or W to read your whispers. the token
+    is never shown or logged
 ";
 
+/// What readchat should do after parsing the arguments
+pub enum Command {
+    /// Join a channel and display its chat
+    Chat,
+    /// Display a previously written transcript
+    View(PathBuf),
+    /// Convert a previously written transcript to another format, optionally only one user's messages
+    Export(PathBuf, export::Format, Option<String>),
+}
+
 pub struct Args {
+    pub command: Command,
     pub channel: String,
     pub nick_max: usize,
+    pub nick_align: Align,
+    pub nick_ellipsis: Ellipsis,
+    pub nick_separator: Option<String>,
     pub buffer_max: usize,
     pub debug: bool,
+    pub debug_script: Option<PathBuf>,
+    pub seed: Option<u64>,
     pub transcribe: bool,
+    pub transcript_format: Format,
     pub timestamps: bool,
+    pub timestamp: Timestamp,
+    pub latency: bool,
     pub min_width: Option<usize>,
+    /// collapse runs of the same message once they're this long
+    pub collapse: Option<usize>,
+    /// the most messages shown a second
+    pub throttle: Option<u32>,
+    pub backlog_max: usize,
+    pub sample: Option<Sample>,
+    /// panes split off the chat, in order
+    pub panes: Vec<PaneSpec>,
+    pub whitespace: Whitespace,
+    pub continuation: Continuation,
+    pub compact_continuation: Continuation,
+    pub mouse: bool,
+    pub links: bool,
+    pub copy_format: clipboard::Format,
+    pub note_deletions: bool,
+    /// only set when chatting as a user, rather than anonymously
+    pub auth: Option<Auth>,
+}
+
+impl Default for Args {
+    fn default() -> Self {
+        Self {
+            command: Command::Chat,
+            channel: "#testing".to_string(),
+            nick_max: 11,
+            nick_align: Align::Left,
+            nick_ellipsis: Ellipsis::Tail,
+            nick_separator: None,
+            buffer_max: 100,
+            debug: false,
+            debug_script: None,
+            seed: None,
+            transcribe: false,
+            transcript_format: Format::Plain,
+            timestamps: false,
+            timestamp: Timestamp::default(),
+            latency: false,
+            min_width: None,
+            collapse: None,
+            throttle: None,
+            backlog_max: 200,
+            sample: None,
+            panes: vec![],
+            whitespace: Whitespace::Collapse,
+            continuation: Continuation::Align,
+            compact_continuation: Continuation::Align,
+            mouse: false,
+            links: false,
+            copy_format: clipboard::Format::Text,
+            note_deletions: false,
+            auth: None,
+        }
+    }
 }
 
 impl Args {
@@ -52,23 +188,101 @@ impl Args {
         }
 
         let nick_max: usize = args.opt_value_from_str(["-n", "--nick-max"])?.unwrap_or(11);
+        let nick_align = args
+            .opt_value_from_str("--nick-align")?
+            .unwrap_or(Align::Left);
+        let nick_ellipsis = args
+            .opt_value_from_str("--nick-ellipsis")?
+            .unwrap_or(Ellipsis::Tail);
+        let nick_separator = args.opt_value_from_str("--nick-separator")?;
+
         let buffer_max: usize = args
             .opt_value_from_str(["-b", "--buffer-max"])?
             .unwrap_or(100);
 
         let min_width = args.opt_value_from_str(["-m", "--min-width"])?;
+        let collapse: Option<usize> = args.opt_value_from_str("--collapse")?;
+        anyhow::ensure!(
+            collapse.map_or(true, |n| n >= 2),
+            "--collapse needs a run of at least 2 messages"
+        );
+        let throttle: Option<u32> = args.opt_value_from_str("--throttle")?;
+        anyhow::ensure!(
+            throttle != Some(0),
+            "--throttle needs at least 1 message a second"
+        );
+        let backlog_max: usize = args.opt_value_from_str("--backlog-max")?.unwrap_or(200);
+        let sample = args.opt_value_from_str("--sample")?;
+        let panes = args.values_from_str("--pane")?;
 
-        let debug = args.contains(["-d", "--debug"]);
+        let debug_script: Option<PathBuf> = args.opt_value_from_str("--debug-script")?;
+        let seed: Option<u64> = args.opt_value_from_str("--seed")?;
+        let debug = args.contains(["-d", "--debug"]) || debug_script.is_some() || seed.is_some();
+        // it's picked up front, so it can be shown before the chat takes over the screen
+        let seed = match debug && debug_script.is_none() {
+            true => Some(seed.unwrap_or_else(|| crate::testing::TestingOpts::load().seed)),
+            false => seed,
+        };
         let transcribe = args.contains(["-t", "--transcribe"]);
         let timestamps = args.contains(["-s", "--timestamp"]);
+        let latency = args.contains("--latency");
+        let timestamp = Timestamp {
+            style: args
+                .opt_value_from_str("--timestamp-format")?
+                .unwrap_or(Timestamp::default().style),
+            zone: match args.contains("--utc") {
+                true => Zone::Utc,
+                false => Zone::Local,
+            },
+        };
+        let transcript_format = match args.contains(["-r", "--raw"]) {
+            true => Format::Irc,
+            false => Format::Plain,
+        };
+
+        let whitespace = match args.contains("--preserve-spaces") {
+            true => Whitespace::Preserve,
+            false => Whitespace::Collapse,
+        };
+
+        let continuation = args
+            .opt_value_from_str("--continuation")?
+            .unwrap_or(Continuation::Align);
+        let compact_continuation = args
+            .opt_value_from_str("--compact-continuation")?
+            .unwrap_or(Continuation::Align);
+
+        let mouse = args.contains("--mouse");
+        let links = args.contains("--links");
+        let note_deletions = args.contains("--note-deletions");
+        let token = args.opt_value_from_str("--token")?;
+        let nick = args.opt_value_from_str("--nick")?;
+        let copy_format = args
+            .opt_value_from_str("--copy-format")?
+            .unwrap_or(clipboard::Format::Text);
+
+        let export_format: Option<export::Format> = args.opt_value_from_str("--format")?;
+        let export_user = args.opt_value_from_str("--user")?;
 
         let mut channels = args.finish();
-        let channel = match channels.len() {
-            _ if debug => "#testing".to_string(),
-            1 => channels.remove(0).into_string().map_err(|s| {
-                // TODO we shouldn't really care if its utf-8 or not. probably
-                anyhow::anyhow!("string contains invalid utf-8, '{}'", s.to_string_lossy())
-            })?,
+        let (command, channel) = match channels.len() {
+            _ if channels.first().and_then(|s| s.to_str()) == Some("view") => {
+                let (path, channel) = transcript_path(&mut channels);
+                (Command::View(path), channel)
+            }
+            _ if channels.first().and_then(|s| s.to_str()) == Some("export") => {
+                let (path, channel) = transcript_path(&mut channels);
+                let format = export_format.unwrap_or(export::Format::Markdown);
+                (Command::Export(path, format, export_user), channel)
+            }
+            _ if debug => (Command::Chat, "#testing".to_string()),
+            1 => (
+                Command::Chat,
+                channels.remove(0).into_string().map_err(|s| {
+                    // TODO we shouldn't really care if its utf-8 or not. probably
+                    anyhow::anyhow!("string contains invalid utf-8, '{}'", s.to_string_lossy())
+                })?,
+            ),
             0 => exit_with_error("ERROR: a channel must be provded"),
             _ => exit_with_error("ERROR: only a single channel can be provded"),
         };
@@ -76,16 +290,72 @@ impl Args {
         // this'll format/correct the channel for us
         let channel = Channel::new(&channel).to_string();
 
+        let auth = match command {
+            Command::Chat => Auth::load(token, nick)?,
+            _ => None,
+        };
+
         Ok(Self {
+            command,
             nick_max,
+            nick_align,
+            nick_ellipsis,
+            nick_separator,
             buffer_max,
             min_width,
+            collapse,
+            throttle,
+            backlog_max,
+            sample,
+            panes,
             channel,
             debug,
+            debug_script,
+            seed,
             transcribe,
+            transcript_format,
             timestamps,
+            timestamp,
+            latency,
+            whitespace,
+            continuation,
+            compact_continuation,
+            mouse,
+            links,
+            copy_format,
+            note_deletions,
+            auth,
         })
     }
+
+    /// The continuation style for wrapped lines in this view mode
+    pub fn continuation(&self, view_mode: ViewMode) -> Continuation {
+        match view_mode {
+            ViewMode::Normal => self.continuation,
+            ViewMode::Compact => self.compact_continuation,
+        }
+    }
+
+    pub(crate) fn continuation_mut(&mut self, view_mode: ViewMode) -> &mut Continuation {
+        match view_mode {
+            ViewMode::Normal => &mut self.continuation,
+            ViewMode::Compact => &mut self.compact_continuation,
+        }
+    }
+}
+
+fn transcript_path(args: &mut Vec<OsString>) -> (PathBuf, String) {
+    if args.len() != 2 {
+        exit_with_error("ERROR: a single transcript must be provided")
+    }
+
+    let path = PathBuf::from(args.remove(1));
+    // transcripts are named after their channel
+    let channel = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "#transcript".to_string());
+    (path, channel)
 }
 
 fn exit_normally(msgs: &[&dyn ToString]) -> ! {
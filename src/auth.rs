@@ -0,0 +1,157 @@
+use std::path::PathBuf;
+
+use anyhow::Context as _;
+
+const TOKEN_VAR: &str = "READCHAT_TOKEN";
+const NICK_VAR: &str = "READCHAT_NICK";
+
+/// The nick the server gives anonymous connections
+pub(crate) const ANONYMOUS: &str = "justinfan1234";
+
+/// Credentials for sending messages as a user
+///
+/// The token is never printed, not even by `Debug`.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Auth {
+    pub nick: String,
+    pub token: String,
+}
+
+impl std::fmt::Debug for Auth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Auth")
+            .field("nick", &self.nick)
+            .field("token", &"<hidden>")
+            .finish()
+    }
+}
+
+impl Auth {
+    /// Finds the credentials in the flags, then the environment, then the config file
+    ///
+    /// Without a token readchat stays anonymous.
+    pub(crate) fn load(
+        token: Option<String>,
+        nick: Option<String>,
+    ) -> anyhow::Result<Option<Self>> {
+        let config = match config_path().filter(|path| path.exists()) {
+            Some(path) => std::fs::read_to_string(&path)
+                .with_context(|| format!("cannot read '{}'", path.display()))?,
+            None => String::new(),
+        };
+        Self::resolve(
+            (token, nick),
+            (std::env::var(TOKEN_VAR).ok(), std::env::var(NICK_VAR).ok()),
+            &config,
+        )
+    }
+
+    // each source is a (token, nick) pair, the first one set wins
+    fn resolve(
+        flags: (Option<String>, Option<String>),
+        env: (Option<String>, Option<String>),
+        config: &str,
+    ) -> anyhow::Result<Option<Self>> {
+        let mut file = (None, None);
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('#') {
+                continue;
+            }
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("token", token)) => file.0 = Some(token.to_string()),
+                Some(("nick", nick)) => file.1 = Some(nick.to_string()),
+                _ => {}
+            }
+        }
+
+        let token = flags.0.or(env.0).or(file.0).filter(|s| !s.is_empty());
+        let nick = flags.1.or(env.1).or(file.1).filter(|s| !s.is_empty());
+
+        let token = match token {
+            Some(token) => token,
+            None => return Ok(None),
+        };
+        let nick = nick.context("a nick is required to use a token, see --nick")?;
+
+        // twitch hands them out without the prefix, but wants it back
+        let token = match token.starts_with("oauth:") {
+            true => token,
+            false => format!("oauth:{}", token),
+        };
+        anyhow::ensure!(
+            token.len() == "oauth:".len() + 30,
+            "a token should be 30 characters long, not counting the 'oauth:'"
+        );
+
+        Ok(Some(Self {
+            nick: nick.to_lowercase(),
+            token,
+        }))
+    }
+}
+
+/// Where the credentials can be kept, so they don't end up in the shell history
+pub fn config_path() -> Option<PathBuf> {
+    let dir = dirs::config_dir()?;
+    Some(dir.join("museun").join("readchat").join("config"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn some(s: &str) -> Option<String> {
+        Some(s.to_string())
+    }
+
+    // tokens are 30 characters
+    fn token(ch: char) -> String {
+        std::iter::repeat(ch).take(30).collect()
+    }
+
+    #[test]
+    fn resolve() {
+        let config = format!(
+            "
+            # set up for the bot
+            nick = shaken_bot
+            token = oauth:{}
+            ",
+            token('c')
+        );
+
+        let auth = Auth::resolve((None, None), (None, None), &config).unwrap();
+        assert_eq!(
+            auth,
+            Some(Auth {
+                nick: "shaken_bot".into(),
+                token: format!("oauth:{}", token('c'))
+            })
+        );
+
+        // flags beat the environment, which beats the file
+        let flags = (Some(token('a')), None);
+        let env = (Some(token('b')), some("Museun"));
+        assert_eq!(
+            Auth::resolve(flags, env, &config).unwrap(),
+            Some(Auth {
+                nick: "museun".into(),
+                token: format!("oauth:{}", token('a'))
+            })
+        );
+
+        assert_eq!(Auth::resolve((None, None), (None, None), "").unwrap(), None);
+        assert!(Auth::resolve((Some(token('a')), None), (None, None), "").is_err());
+        assert!(Auth::resolve((some("short"), some("museun")), (None, None), "").is_err());
+    }
+
+    #[test]
+    fn hidden() {
+        let auth = Auth {
+            nick: "museun".into(),
+            token: "oauth:secret".into(),
+        };
+        let debug = format!("{:?}", auth);
+        assert!(!debug.contains("secret"), "{}", debug);
+    }
+}
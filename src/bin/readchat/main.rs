@@ -13,7 +13,6 @@ fn main() -> anyhow::Result<()> {
         let mut fi = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .write(true)
             .open("panics.log")
             .unwrap();
 
@@ -29,12 +28,22 @@ fn main() -> anyhow::Result<()> {
 
     let args = readchat::Args::parse()?;
 
-    let logger = if args.debug {
+    if let readchat::Command::Export(path, format, user) = &args.command {
+        let out = &mut std::io::stdout().lock();
+        return readchat::export(path, &args.channel, *format, user.as_deref(), out);
+    }
+
+    let logger = if args.debug || matches!(args.command, readchat::Command::View(..)) {
         readchat::Logger::default()
     } else {
         readchat::Logger::from_xdg(&args.channel)?
     };
 
-    let _screen = readchat::AltScreen::enter();
+    // the alternate screen is thrown away on exit, so the seed is written where it's kept
+    if let Some(seed) = args.seed.filter(|_| args.debug_script.is_none()) {
+        eprintln!("seed: {}", seed);
+    }
+
+    let _screen = readchat::AltScreen::enter(args.mouse);
     readchat::App::run(args, logger)
 }
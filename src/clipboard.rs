@@ -0,0 +1,124 @@
+use std::io::Write;
+
+use twitchchat::messages::Privmsg;
+
+/// What is copied from a message
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Just the text
+    Text,
+    /// `nick: text`, like the plain transcripts
+    Line,
+    /// The irc line, with its tags
+    Raw,
+}
+
+impl Format {
+    /// The next format, for cycling through them
+    pub(crate) const fn next(self) -> Self {
+        match self {
+            Self::Text => Self::Line,
+            Self::Line => Self::Raw,
+            Self::Raw => Self::Text,
+        }
+    }
+
+    pub(crate) fn render(self, pm: &Privmsg<'_>) -> String {
+        match self {
+            Self::Text => pm.data().to_string(),
+            Self::Line => format!("{}: {}", pm.name(), pm.data()),
+            Self::Raw => pm.raw().trim_end().to_string(),
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(Self::Text),
+            "line" => Ok(Self::Line),
+            "raw" => Ok(Self::Raw),
+            s => anyhow::bail!("unknown copy format '{}', expected text, line or raw", s),
+        }
+    }
+}
+
+impl std::fmt::Display for Format {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Text => "text",
+            Self::Line => "line",
+            Self::Raw => "raw",
+        })
+    }
+}
+
+/// Copies `text` to the system clipboard with an OSC 52 sequence
+///
+/// The terminal does the copying, so this also works over ssh.
+pub(crate) fn copy(out: &mut impl Write, text: &str) -> anyhow::Result<()> {
+    write!(out, "\x1b]52;c;{}\x07", base64(text.as_bytes()))?;
+    out.flush()?;
+    Ok(())
+}
+
+pub(crate) fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0_u32, |n, (i, &b)| n | (b as u32) << (16 - i * 8));
+
+        // a chunk of n bytes is encoded in n + 1 characters, the rest is padding
+        for i in 0..4 {
+            match i <= chunk.len() {
+                true => out.push(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3f] as char),
+                false => out.push('='),
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64() {
+        let tests = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foobar", "Zm9vYmFy"),
+            ("héllo wörld", "aMOpbGxvIHfDtnJsZA=="),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(super::base64(input.as_bytes()), expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn render() {
+        let pm = crate::transcript::parse_privmsg(
+            "@color=#FF0000 :museun!museun@museun PRIVMSG #museun :hello world",
+        )
+        .unwrap();
+        assert_eq!(Format::Text.render(&pm), "hello world");
+        assert_eq!(Format::Line.render(&pm), "museun: hello world");
+        assert_eq!(
+            Format::Raw.render(&pm),
+            "@color=#FF0000 :museun!museun@museun PRIVMSG #museun :hello world"
+        );
+
+        let mut out = vec![];
+        copy(&mut out, "hi").unwrap();
+        assert_eq!(out, b"\x1b]52;c;aGk=\x07");
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+/// Where the app gets the time from, for throttling and the like
+///
+/// The tests use one they move along by hand, rather than sleeping.
+#[derive(Clone, Default)]
+pub(crate) struct Clock {
+    manual: Option<Rc<Cell<Instant>>>,
+}
+
+impl Clock {
+    /// A clock that only moves when it's advanced
+    #[cfg(test)]
+    pub(crate) fn manual() -> Self {
+        Self {
+            manual: Some(Rc::new(Cell::new(Instant::now()))),
+        }
+    }
+
+    pub(crate) fn now(&self) -> Instant {
+        match &self.manual {
+            Some(now) => now.get(),
+            None => Instant::now(),
+        }
+    }
+
+    #[cfg(test)]
+    pub(crate) fn advance(&self, by: Duration) {
+        if let Some(now) = &self.manual {
+            now.set(now.get() + by);
+        }
+    }
+
+    pub(crate) fn elapsed(&self, since: Instant) -> Duration {
+        self.now().saturating_duration_since(since)
+    }
+}
@@ -0,0 +1,373 @@
+use std::{fmt::Display, io::Write, path::Path};
+
+use chrono::TimeZone;
+use twitchchat::messages::Privmsg;
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::{transcript, truncate, window::Message};
+
+const TS_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// The formats a transcript can be exported to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Html,
+    Markdown,
+    Csv,
+}
+
+impl std::str::FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html" => Ok(Self::Html),
+            "md" | "markdown" => Ok(Self::Markdown),
+            "csv" => Ok(Self::Csv),
+            s => anyhow::bail!("unknown format '{}', expected html, md or csv", s),
+        }
+    }
+}
+
+/// Converts the transcript at `path` to `format`, writing it to `out`
+///
+/// With a `user`, only their messages are written.
+pub fn export(
+    path: &Path,
+    channel: &str,
+    format: Format,
+    user: Option<&str>,
+    out: &mut impl Write,
+) -> anyhow::Result<()> {
+    let messages = transcript::load(path, channel)?
+        .into_iter()
+        .enumerate()
+        .map(|(id, entry)| Message::new(id, entry.pm, entry.ts))
+        .collect::<Vec<_>>();
+
+    let messages = match user {
+        Some(name) => messages
+            .into_iter()
+            .filter(|msg| is_from(&msg.pm, name))
+            .collect(),
+        None => messages,
+    };
+
+    format.write(channel, &messages, &chrono::Local, out)
+}
+
+// by their login or display name, in any case
+fn is_from(pm: &Privmsg<'_>, name: &str) -> bool {
+    pm.name().eq_ignore_ascii_case(name)
+        || pm
+            .display_name()
+            .is_some_and(|display| display.to_lowercase() == name.to_lowercase())
+}
+
+impl Format {
+    pub(crate) fn write<Tz>(
+        &self,
+        channel: &str,
+        messages: &[Message<'_>],
+        tz: &Tz,
+        out: &mut impl Write,
+    ) -> anyhow::Result<()>
+    where
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        let write = match self {
+            Self::Html => html,
+            Self::Markdown => markdown,
+            Self::Csv => csv,
+        };
+
+        let rows = messages
+            .iter()
+            .map(|msg| Row::new(msg, tz))
+            .collect::<Vec<_>>();
+
+        write(channel, &rows, out)?;
+        out.flush()?;
+        Ok(())
+    }
+}
+
+struct Row<'a> {
+    ts: String,
+    login: &'a str,
+    name: &'a str,
+    color: Option<String>,
+    badges: Vec<(&'a str, &'a str)>,
+    data: &'a str,
+}
+
+impl<'a> Row<'a> {
+    fn new<Tz>(msg: &'a Message<'_>, tz: &Tz) -> Self
+    where
+        Tz: TimeZone,
+        Tz::Offset: Display,
+    {
+        let pm = &msg.pm;
+        Self {
+            ts: msg.ts.with_timezone(tz).format(TS_FORMAT).to_string(),
+            login: pm.name(),
+            name: pm.display_name().unwrap_or_else(|| pm.name()),
+            color: pm.color().map(|color| color.rgb.to_string()),
+            badges: badges(pm),
+            data: pm.data(),
+        }
+    }
+}
+
+// (name, version) pairs, in the order twitch sent them
+fn badges<'a>(pm: &'a Privmsg<'_>) -> Vec<(&'a str, &'a str)> {
+    pm.tags()
+        .get("badges")
+        .into_iter()
+        .flat_map(|badges| badges.split(','))
+        .filter_map(|badge| badge.split_once('/'))
+        .collect()
+}
+
+fn html(channel: &str, rows: &[Row<'_>], out: &mut dyn Write) -> anyhow::Result<()> {
+    const STYLE: &str = "\
+body { background: #18181b; color: #efeff1; font-family: sans-serif; }
+.message { padding: 2px 0; }
+.ts { color: #adadb8; font-family: monospace; }
+.badge { border: 1px solid #adadb8; border-radius: 3px; font-size: 0.75em; margin-right: 2px; padding: 0 2px; }
+.name { font-weight: bold; }";
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>")?;
+    writeln!(out, "<head>")?;
+    writeln!(out, "<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>{}</title>", escape_html(channel))?;
+    writeln!(out, "<style>\n{}\n</style>", STYLE)?;
+    writeln!(out, "</head>")?;
+    writeln!(out, "<body>")?;
+
+    for row in rows {
+        write!(
+            out,
+            "<div class=\"message\"><span class=\"ts\">{}</span> ",
+            row.ts
+        )?;
+
+        for (badge, version) in &row.badges {
+            write!(
+                out,
+                "<span class=\"badge {badge}\" title=\"{badge}/{version}\">{badge}</span>",
+                badge = escape_html(badge),
+                version = escape_html(version)
+            )?;
+        }
+
+        match &row.color {
+            Some(color) => write!(out, "<span class=\"name\" style=\"color: {}\"", color)?,
+            None => write!(out, "<span class=\"name\"")?,
+        }
+
+        writeln!(
+            out,
+            " title=\"{}\">{}</span>: <span class=\"text\">{}</span></div>",
+            escape_html(row.login),
+            escape_html(row.name),
+            escape_html(row.data)
+        )?;
+    }
+
+    writeln!(out, "</body>")?;
+    writeln!(out, "</html>")?;
+    Ok(())
+}
+
+fn markdown(channel: &str, rows: &[Row<'_>], out: &mut dyn Write) -> anyhow::Result<()> {
+    const HEADER: [&str; 3] = ["time", "name", "message"];
+
+    let names = rows
+        .iter()
+        .map(|row| escape_markdown(row.name))
+        .collect::<Vec<_>>();
+
+    // the columns are padded to their display width so the source lines up
+    let ts_width = rows
+        .iter()
+        .map(|row| row.ts.width())
+        .chain(std::iter::once(HEADER[0].width()))
+        .max()
+        .unwrap_or_default();
+
+    let name_width = names
+        .iter()
+        .map(|name| name.width())
+        .chain(std::iter::once(HEADER[1].width()))
+        .max()
+        .unwrap_or_default();
+
+    writeln!(out, "### {}", escape_markdown(channel))?;
+    writeln!(out)?;
+    writeln!(
+        out,
+        "| {} | {} | {} |",
+        truncate::truncate_or_pad(HEADER[0], ts_width),
+        truncate::truncate_or_pad(HEADER[1], name_width),
+        HEADER[2]
+    )?;
+    writeln!(
+        out,
+        "| {} | {} | {} |",
+        "-".repeat(ts_width),
+        "-".repeat(name_width),
+        "-".repeat(HEADER[2].width())
+    )?;
+
+    for (row, name) in rows.iter().zip(names) {
+        writeln!(
+            out,
+            "| {} | {} | {} |",
+            truncate::truncate_or_pad(&row.ts, ts_width),
+            truncate::truncate_or_pad(&name, name_width),
+            escape_markdown(row.data)
+        )?;
+    }
+
+    Ok(())
+}
+
+fn csv(_channel: &str, rows: &[Row<'_>], out: &mut dyn Write) -> anyhow::Result<()> {
+    const HEADER: &str = "timestamp,login,display_name,color,badges,message";
+
+    write!(out, "{}\r\n", HEADER)?;
+    for row in rows {
+        let badges = row
+            .badges
+            .iter()
+            .map(|(badge, version)| format!("{}/{}", badge, version))
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let fields = [
+            &*row.ts,
+            row.login,
+            row.name,
+            row.color.as_deref().unwrap_or_default(),
+            &badges,
+            row.data,
+        ];
+
+        let line = fields
+            .iter()
+            .map(|field| escape_csv(field))
+            .collect::<Vec<_>>()
+            .join(",");
+
+        write!(out, "{}\r\n", line)?;
+    }
+    Ok(())
+}
+
+fn escape_html(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for ch in input.chars() {
+        match ch {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+fn escape_markdown(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    for ch in input.chars() {
+        if matches!(
+            ch,
+            '\\' | '|' | '*' | '_' | '`' | '<' | '>' | '[' | ']' | '#' | '~'
+        ) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+fn escape_csv(input: &str) -> std::borrow::Cow<'_, str> {
+    if input.contains(&[',', '"', '\r', '\n'][..]) {
+        return format!("\"{}\"", input.replace('"', "\"\"")).into();
+    }
+    input.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn golden(format: Format, name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden");
+
+        let messages = transcript::load(&dir.join("transcript.log"), "#museun")
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(id, entry)| Message::new(id, entry.pm, entry.ts))
+            .collect::<Vec<_>>();
+
+        let mut out = vec![];
+        format
+            .write("#museun", &messages, &chrono::Utc, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // set this to regenerate the expected output
+        if std::env::var("READCHAT_BLESS").is_ok() {
+            std::fs::write(dir.join(name), &out).unwrap();
+        }
+
+        let expected = std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(out, expected, "output differs from tests/golden/{}", name);
+    }
+
+    #[test]
+    fn html() {
+        golden(Format::Html, "export.html")
+    }
+
+    #[test]
+    fn markdown() {
+        golden(Format::Markdown, "export.md")
+    }
+
+    #[test]
+    fn csv() {
+        golden(Format::Csv, "export.csv")
+    }
+
+    #[test]
+    fn user() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("golden")
+            .join("transcript.log");
+
+        let mut out = vec![];
+        export(&path, "#museun", Format::Csv, Some("MUSEUN"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let logins = out
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(logins, ["museun", "museun"]);
+
+        let mut out = vec![];
+        export(&path, "#museun", Format::Csv, Some("nobody"), &mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 1);
+    }
+}
@@ -0,0 +1,62 @@
+use twitchchat::messages::Privmsg;
+
+use crate::reply;
+
+/// A predicate for narrowing down which messages are shown
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Filter {
+    /// `@name` only shows messages from that user
+    Name(String),
+    /// anything else is matched against the message text
+    Text(String),
+    /// the messages of a reply thread, by the id of the message that started it
+    Thread(String),
+    /// messages from the broadcaster, moderators and vips
+    Mods,
+    /// messages that were highlighted with channel points
+    Highlights,
+}
+
+impl Filter {
+    pub(crate) fn parse(input: &str) -> Option<Self> {
+        let input = input.trim();
+        match input.strip_prefix('@') {
+            Some(name) if !name.is_empty() => Some(Self::Name(name.to_lowercase())),
+            Some(..) => None,
+            None if !input.is_empty() => Some(Self::Text(input.to_string())),
+            None => None,
+        }
+    }
+
+    pub(crate) fn matches(&self, pm: &Privmsg<'_>) -> bool {
+        match self {
+            Self::Name(name) => {
+                pm.name().eq_ignore_ascii_case(name)
+                    || pm
+                        .display_name()
+                        .filter(|s| s.to_lowercase() == *name)
+                        .is_some()
+            }
+            Self::Text(text) => contains_ignore_case(pm.data(), text),
+            Self::Thread(id) => reply::thread(pm).as_ref() == Some(id),
+            Self::Mods => pm.is_broadcaster() || pm.is_moderator() || pm.is_vip(),
+            Self::Highlights => pm.tags().get("msg-id") == Some("highlighted-message"),
+        }
+    }
+}
+
+impl std::fmt::Display for Filter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Name(name) => write!(f, "@{}", name),
+            Self::Text(text) => f.write_str(text),
+            Self::Thread(..) => f.write_str("thread"),
+            Self::Mods => f.write_str("mods"),
+            Self::Highlights => f.write_str("highlights"),
+        }
+    }
+}
+
+pub(crate) fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
@@ -0,0 +1,233 @@
+use std::io::Write;
+
+use crossterm::{cursor::*, terminal::*};
+
+use crate::pane::Rect;
+
+// the terminal shows everything between these at once, if it supports synchronized updates
+const BEGIN_SYNC: &[u8] = b"\x1b[?2026h";
+const END_SYNC: &[u8] = b"\x1b[?2026l";
+
+/// What's on the screen, so drawing it again only has to write what changed
+///
+/// Each row is kept as the bytes that drew it, a row that would be drawn the
+/// same way again is left alone. Rather than clearing the screen, the rows
+/// that changed are overwritten in place.
+#[derive(Default)]
+pub(crate) struct Frame {
+    size: (u16, u16),
+    // None is a row we don't know the contents of, e.g. after a resize
+    rows: Vec<Option<Vec<u8>>>,
+    // the row the last message was appended on, like the layout's cursor
+    cursor: usize,
+}
+
+impl Frame {
+    /// Draws `canvas`, writing only the rows that are different from the last frame
+    pub(crate) fn draw(&mut self, stdout: &mut impl Write, canvas: Canvas) -> anyhow::Result<()> {
+        self.resize(canvas.size);
+
+        let mut out = vec![];
+        for (y, row) in canvas.rows.into_iter().enumerate() {
+            if self.rows[y].as_ref() == Some(&row) {
+                continue;
+            }
+            crossterm::queue!(out, MoveTo(0, y as _), Clear(ClearType::CurrentLine))?;
+            out.extend_from_slice(&row);
+            self.rows[y] = Some(row);
+        }
+        self.cursor = canvas.cursor;
+        self.flush(stdout, &out)
+    }
+
+    /// Draws a single row, e.g. the status line
+    pub(crate) fn draw_row(
+        &mut self,
+        stdout: &mut impl Write,
+        size: (u16, u16),
+        y: u16,
+        row: Vec<u8>,
+    ) -> anyhow::Result<()> {
+        self.resize(size);
+
+        let old = match self.rows.get_mut(y as usize) {
+            Some(old) if old.as_ref() != Some(&row) => old,
+            _ => return Ok(()),
+        };
+        let mut out = vec![];
+        crossterm::queue!(out, MoveTo(0, y), Clear(ClearType::CurrentLine))?;
+        out.extend_from_slice(&row);
+        *old = Some(row);
+        self.flush(stdout, &out)
+    }
+
+    /// Messages were printed a line feed at a time, scrolling the screen once they reached the bottom
+    pub(crate) fn append(&mut self, size: (u16, u16), rendered: &[u8]) {
+        self.resize(size);
+        if self.rows.is_empty() {
+            return;
+        }
+
+        // the first line is the rest of the row the cursor was on
+        for line in lines(rendered).skip(1) {
+            if self.cursor + 1 < self.rows.len() {
+                self.cursor += 1;
+            } else {
+                self.rows.remove(0);
+                self.rows.push(None);
+            }
+            self.rows[self.cursor] = Some(line.to_vec());
+        }
+    }
+
+    /// Forgets what's on the screen, e.g. after something else wrote to it
+    ///
+    /// The next frame is drawn in full.
+    pub(crate) fn invalidate(&mut self) {
+        self.rows.iter_mut().for_each(|row| *row = None);
+    }
+
+    /// The cursor was moved to the top, to append messages from there
+    pub(crate) fn home(&mut self) {
+        self.cursor = 0;
+    }
+
+    fn resize(&mut self, size: (u16, u16)) {
+        if self.size == size {
+            return;
+        }
+        self.size = size;
+        self.rows = vec![None; size.1 as _];
+        self.cursor = self.cursor.min((size.1 as usize).saturating_sub(1));
+    }
+
+    fn flush(&self, stdout: &mut impl Write, out: &[u8]) -> anyhow::Result<()> {
+        if out.is_empty() {
+            return Ok(());
+        }
+        stdout.write_all(BEGIN_SYNC)?;
+        stdout.write_all(out)?;
+        // messages are appended from where the last one was printed
+        crossterm::queue!(stdout, MoveTo(0, self.cursor as _))?;
+        stdout.write_all(END_SYNC)?;
+        Ok(())
+    }
+}
+
+/// The next frame, before it's drawn
+///
+/// Everything is written to the row it starts on, moving the cursor there
+/// first. Nothing is written to the terminal until it's drawn by the [`Frame`].
+pub(crate) struct Canvas {
+    size: (u16, u16),
+    rows: Vec<Vec<u8>>,
+    /// the row the last message is on, where the next one gets appended
+    pub(crate) cursor: usize,
+    // anything written below the screen ends up here, and is thrown away
+    clipped: Vec<u8>,
+}
+
+impl Canvas {
+    pub(crate) fn new(size: (u16, u16)) -> Self {
+        Self {
+            size,
+            rows: vec![vec![]; size.1 as _],
+            cursor: 0,
+            clipped: vec![],
+        }
+    }
+
+    /// Where to write something that starts at column `x` of row `y`
+    pub(crate) fn at(&mut self, x: u16, y: u16) -> anyhow::Result<&mut Vec<u8>> {
+        let row = match self.rows.get_mut(y as usize) {
+            Some(row) => row,
+            None => {
+                self.clipped.clear();
+                return Ok(&mut self.clipped);
+            }
+        };
+        // a row is drawn starting from the left edge
+        if x > 0 || !row.is_empty() {
+            crossterm::queue!(row, MoveTo(x, y))?;
+        }
+        Ok(row)
+    }
+
+    /// Puts the lines of `rendered` in `rect`, returning the row of the last one
+    ///
+    /// Messages are printed a line feed at a time, expecting the screen to
+    /// scroll them up. This keeps the lines that would've been left in `rect`.
+    pub(crate) fn put_rendered(&mut self, rendered: &[u8], rect: Rect) -> anyhow::Result<usize> {
+        let lines = lines(rendered).collect::<Vec<_>>();
+        let skip = lines.len().saturating_sub(rect.height as usize);
+        let mut last = rect.y as usize;
+        for (row, line) in lines.into_iter().skip(skip).enumerate() {
+            last = rect.y as usize + row;
+            self.at(rect.x, last as _)?.extend_from_slice(line);
+        }
+        Ok(last)
+    }
+}
+
+// the printed lines, each starts at the left of wherever it's put rather than of the screen
+fn lines(rendered: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut start = vec![];
+    // writing to a Vec can't fail
+    let _ = crossterm::queue!(start, MoveToColumn(0));
+
+    rendered
+        .split(|&b| b == b'\n')
+        .map(move |line| line.strip_prefix(&*start).unwrap_or(line))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::style::Print;
+
+    fn canvas(lines: &[&str]) -> Canvas {
+        let mut canvas = Canvas::new((10, lines.len() as _));
+        for (y, line) in lines.iter().enumerate() {
+            canvas
+                .at(0, y as _)
+                .unwrap()
+                .extend_from_slice(line.as_bytes());
+        }
+        canvas.cursor = lines.len() - 1;
+        canvas
+    }
+
+    #[test]
+    fn draw() {
+        let mut frame = Frame::default();
+        let mut out = vec![];
+        frame.draw(&mut out, canvas(&["a", "b", "c"])).unwrap();
+        assert!(out.starts_with(BEGIN_SYNC) && out.ends_with(END_SYNC));
+
+        // only the row that changed is drawn again
+        out.clear();
+        frame.draw(&mut out, canvas(&["a", "d", "c"])).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("\x1b[2;1H\x1b[2Kd"));
+        assert!(!out.contains('a') && !out.contains('c'));
+
+        let mut out = vec![];
+        frame.draw(&mut out, canvas(&["a", "d", "c"])).unwrap();
+        assert!(out.is_empty());
+
+        // appended lines scroll the rest up
+        let mut rendered = vec![];
+        crossterm::queue!(rendered, Print("\n"), MoveToColumn(0), Print("e")).unwrap();
+        frame.append((10, 3), &rendered);
+        let mut out = vec![];
+        frame.draw(&mut out, canvas(&["d", "c", "e"])).unwrap();
+        assert!(out.is_empty());
+
+        // everything is drawn again once the screen is unknown
+        frame.invalidate();
+        let mut out = vec![];
+        frame.draw(&mut out, canvas(&["d", "c", "e"])).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains('d') && out.contains('c') && out.contains('e'));
+    }
+}
@@ -0,0 +1,205 @@
+use std::time::Duration;
+
+use anyhow::Context as _;
+use flume::{Receiver, Sender};
+use serde_json::{json, Value};
+
+use crate::{
+    auth::Auth,
+    moderate::{Pending, Request, User},
+    twitch::Notice,
+};
+
+// how long to wait on twitch before giving up on a request
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Where twitch's api is, the tests point these at a fake one
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Urls {
+    /// where the helix endpoints are
+    pub(crate) api: String,
+    /// where tokens are validated
+    pub(crate) id: String,
+}
+
+impl Default for Urls {
+    fn default() -> Self {
+        Self {
+            api: "https://api.twitch.tv/helix".into(),
+            id: "https://id.twitch.tv/oauth2".into(),
+        }
+    }
+}
+
+/// Sends the moderation actions from a background thread, as twitch stopped taking
+/// them as chat commands. How each one went is sent to `notify`
+pub(crate) fn spawn(urls: Urls, auth: Auth, notify: Sender<Notice>) -> Sender<Pending> {
+    let (moderation, pending) = flume::unbounded();
+    let _ = std::thread::spawn(move || run(&urls, &auth, &pending, &notify));
+    moderation
+}
+
+fn run(urls: &Urls, auth: &Auth, pending: &Receiver<Pending>, notify: &Sender<Notice>) {
+    let mut client = None;
+    for pending in pending.iter() {
+        let text = match send(&mut client, urls, auth, &pending) {
+            Ok(()) => pending.done,
+            Err(err) => format!("{} failed: {}", pending.question.trim_end_matches('?'), err),
+        };
+        if notify.send(Notice::Text(text)).is_err() {
+            break;
+        }
+    }
+}
+
+// the token is checked when it's first needed, rather than on startup
+fn send(
+    client: &mut Option<Client>,
+    urls: &Urls,
+    auth: &Auth,
+    pending: &Pending,
+) -> anyhow::Result<()> {
+    let client = match client {
+        Some(client) => client,
+        None => client.insert(Client::validate(urls, auth)?),
+    };
+    client.send(pending.broadcaster, &pending.request)
+}
+
+struct Client {
+    agent: ureq::Agent,
+    api: String,
+    token: String,
+    client_id: String,
+    /// who the token belongs to, the moderator
+    user_id: String,
+}
+
+impl Client {
+    // the api wants to know which app the token is for, and who it belongs to
+    fn validate(urls: &Urls, auth: &Auth) -> anyhow::Result<Self> {
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+        let token = auth.token.trim_start_matches("oauth:").to_string();
+
+        let resp: Value = agent
+            .get(&format!("{}/validate", urls.id))
+            .set("Authorization", &format!("OAuth {}", token))
+            .call()
+            .map_err(error)?
+            .into_json()?;
+        let field = |key: &str| {
+            resp[key]
+                .as_str()
+                .map(ToString::to_string)
+                .with_context(|| format!("twitch didn't say what the token's {} is", key))
+        };
+
+        Ok(Self {
+            client_id: field("client_id")?,
+            user_id: field("user_id")?,
+            api: urls.api.clone(),
+            agent,
+            token,
+        })
+    }
+
+    fn send(&self, broadcaster: u64, request: &Request) -> anyhow::Result<()> {
+        let (method, endpoint, query, body) = match request {
+            Request::Delete(id) => (
+                "DELETE",
+                "moderation/chat",
+                Some(("message_id", id.clone())),
+                None,
+            ),
+            Request::Ban(user, secs) => {
+                let mut data = json!({ "user_id": self.user_id(user)? });
+                if let Some(secs) = secs {
+                    data["duration"] = json!(secs);
+                }
+                (
+                    "POST",
+                    "moderation/bans",
+                    None,
+                    Some(json!({ "data": data })),
+                )
+            }
+            Request::Unban(user) => (
+                "DELETE",
+                "moderation/bans",
+                Some(("user_id", self.user_id(user)?)),
+                None,
+            ),
+            Request::Slow(Some(secs)) => (
+                "PATCH",
+                "chat/settings",
+                None,
+                Some(json!({ "slow_mode": true, "slow_mode_wait_time": secs })),
+            ),
+            Request::Slow(None) => (
+                "PATCH",
+                "chat/settings",
+                None,
+                Some(json!({ "slow_mode": false })),
+            ),
+            Request::SubsOnly(on) => (
+                "PATCH",
+                "chat/settings",
+                None,
+                Some(json!({ "subscriber_mode": on })),
+            ),
+        };
+
+        let mut req = self
+            .request(method, endpoint)
+            .query("broadcaster_id", &broadcaster.to_string())
+            .query("moderator_id", &self.user_id);
+        if let Some((key, value)) = query {
+            req = req.query(key, &value);
+        }
+        match body {
+            Some(body) => req.send_json(body),
+            None => req.call(),
+        }
+        .map_err(error)?;
+        Ok(())
+    }
+
+    // messages from transcripts (and older servers) don't have the user's id
+    fn user_id(&self, user: &User) -> anyhow::Result<String> {
+        if let Some(id) = &user.id {
+            return Ok(id.clone());
+        }
+
+        let resp: Value = self
+            .request("GET", "users")
+            .query("login", &user.login)
+            .call()
+            .map_err(error)?
+            .into_json()?;
+        resp["data"][0]["id"]
+            .as_str()
+            .map(ToString::to_string)
+            .with_context(|| format!("there is no user named {}", user.login))
+    }
+
+    fn request(&self, method: &str, endpoint: &str) -> ureq::Request {
+        self.agent
+            .request(method, &format!("{}/{}", self.api, endpoint))
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .set("Client-Id", &self.client_id)
+    }
+}
+
+// twitch says what was wrong in the body, e.g. that the token is missing a scope
+fn error(err: ureq::Error) -> anyhow::Error {
+    match err {
+        ureq::Error::Status(status, resp) => {
+            let message = resp
+                .into_json::<Value>()
+                .ok()
+                .and_then(|body| body["message"].as_str().map(ToString::to_string));
+            anyhow::anyhow!(message.unwrap_or_else(|| format!("twitch answered with {}", status)))
+        }
+        err => err.into(),
+    }
+}
@@ -1,4 +1,4 @@
-use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers, MouseButton, MouseEvent, MouseEventKind};
 use flume::Sender;
 
 #[rustfmt::skip]
@@ -12,40 +12,154 @@ macro_rules! key {
 
 const fn is_mark(ch: char, mod_: KeyModifiers) -> bool {
     (matches!(ch, 'a'..='z' | '0'..='9') && matches!(mod_, KeyModifiers::NONE))
-        || (matches!(ch, 'A'..='Z') && matches!(mod_, KeyModifiers::SHIFT))
+        || (ch.is_ascii_uppercase() && matches!(mod_, KeyModifiers::SHIFT))
 }
 
-pub fn handle(event: KeyEvent, events: &Sender<Message>) {
+const fn is_input(mod_: KeyModifiers) -> bool {
+    matches!(mod_, KeyModifiers::NONE | KeyModifiers::SHIFT)
+}
+
+/// How key presses should be interpreted
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    Normal,
+    /// letters select a mark
+    Marking,
+    /// keys are typed into the prompt
+    Input,
+}
+
+pub fn handle(event: KeyEvent, mode: Mode, events: &Sender<Message>) {
     #[rustfmt::skip]
     macro_rules! send { ($ev:tt) => {{ let _ = events.send(Message::$ev); }}; }
 
     #[allow(unreachable_patterns)] // somehow this is a false positive
-    match event {
-        key!(ctrl 'c') => send!(Quit),
-        key!(ctrl 'r') => send!(Redraw),
-        key!(ctrl 'd') => send!(Delete),
+    match (event, mode) {
+        (key!(ctrl 'c'), ..) => send!(Quit),
+        (key!(ctrl 'r'), ..) => send!(Redraw),
+
+        (key!(Esc), Mode::Input) => send!(Cancel),
+        (key!(Enter), Mode::Input) => send!(Submit),
+        (key!(Backspace), Mode::Input) => send!(Backspace),
+        #[rustfmt::skip]
+        (KeyEvent { code: KeyCode::Char(ch), modifiers }, Mode::Input) if is_input(modifiers) => {
+            let _ = events.send(Message::Input(ch));
+        }
+        (_, Mode::Input) => {}
 
-        key!(char '[') | key!(shift '<') => send!(NameColumnShrink),
-        key!(char ']') | key!(shift '>') => send!(NameColumnGrow),
+        (key!(ctrl 'd'), ..) => send!(Delete),
+        (key!(Delete), Mode::Normal) => send!(DeleteSelected),
+        (key!(char 'u'), Mode::Normal) => send!(Undo),
 
-        key!(char 't') => send!(ToggleTimestamps),
+        (key!(Esc), Mode::Marking) => send!(Cancel),
+        (key!(Tab), Mode::Marking) => send!(CycleMarking),
+        (key!(Enter), Mode::Marking) => send!(Submit),
+        (key!(char '*') | key!(shift '*'), Mode::Marking) => send!(CopyShown),
 
         #[rustfmt::skip]
-        KeyEvent { code: KeyCode::Char(ch), modifiers } if is_mark(ch, modifiers) => {
+        (KeyEvent { code: KeyCode::Char(ch), modifiers }, Mode::Marking) if is_mark(ch, modifiers) => {
             let _ = events.send(Message::Char(ch));
         }
+        (_, Mode::Marking) => {}
+
+        (key!(char '[') | key!(shift '<'), ..) => send!(NameColumnShrink),
+        (key!(char ']') | key!(shift '>'), ..) => send!(NameColumnGrow),
+
+        (key!(char 't'), ..) => send!(ToggleTimestamps),
+        (key!(char 'w'), ..) => send!(ToggleContinuation),
+
+        (key!(char 'y'), ..) => send!(Copy),
+        (key!(char 'm'), ..) => send!(Moderate),
+        (key!(char 'r'), ..) => send!(Thread),
+        (key!(char 'i'), ..) => send!(UserInfo),
+        (key!(char 'x'), ..) => send!(Mute),
+        (key!(shift 'W'), ..) => send!(Whispers),
+        (key!(char 's'), ..) => send!(Stats),
+        (key!(shift 'Y'), ..) => send!(CopyLast),
+
+        (key!(Up) | key!(char 'k'), ..) => send!(ScrollUp),
+        (key!(Down) | key!(char 'j'), ..) => send!(ScrollDown),
+        (key!(PageUp), ..) => send!(PageUp),
+        (key!(PageDown), ..) => send!(PageDown),
+        (key!(Home), ..) => send!(ScrollTop),
+        (key!(End), ..) => send!(ScrollBottom),
+
+        (key!(char '/'), ..) => send!(Search),
+        (key!(char 'n'), ..) => send!(SearchOlder),
+        (key!(shift 'N'), ..) => send!(SearchNewer),
+        (key!(char 'f'), ..) => send!(Filter),
+        (key!(Enter), ..) => send!(Compose),
+        (key!(Esc), ..) => send!(Cancel),
 
         _ => {}
     }
 }
 
+pub fn handle_mouse(event: MouseEvent, mode: Mode, events: &Sender<Message>) {
+    // the marks and the prompt are only driven by the keyboard
+    if mode != Mode::Normal {
+        return;
+    }
+
+    let msg = match event.kind {
+        MouseEventKind::ScrollUp => Message::WheelUp,
+        MouseEventKind::ScrollDown => Message::WheelDown,
+        MouseEventKind::Down(MouseButton::Left) => Message::Click(event.column, event.row),
+        _ => return,
+    };
+    let _ = events.send(msg);
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum Message {
     Quit,
     Redraw,
     Delete,
+    DeleteSelected,
     Char(char),
+    Copy,
+    CopyLast,
+    CopyShown,
+    /// changes what the marks do, e.g. the copy format
+    CycleMarking,
+    Undo,
     NameColumnGrow,
     NameColumnShrink,
     ToggleTimestamps,
+    ToggleContinuation,
+
+    ScrollUp,
+    ScrollDown,
+    PageUp,
+    PageDown,
+    ScrollTop,
+    ScrollBottom,
+    WheelUp,
+    WheelDown,
+    /// a click at this column and row
+    Click(u16, u16),
+
+    Search,
+    SearchOlder,
+    SearchNewer,
+    Filter,
+
+    Input(char),
+    Backspace,
+    Submit,
+    /// start writing a message to send
+    Compose,
+    /// pick a message to act on as a moderator
+    Moderate,
+    /// show only the reply thread of a message
+    Thread,
+    /// show the whispers
+    Whispers,
+    /// show how active the chat has been
+    Stats,
+    /// show what has been seen of a message's author
+    UserInfo,
+    /// hide everything from a message's author, including what they send later
+    Mute,
+    Cancel,
 }
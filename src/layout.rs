@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+/// What was printed on a row of the screen
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Row {
+    /// the id of the message this row is a part of
+    pub(crate) id: usize,
+    /// the columns the name was printed in, if it's on this row
+    pub(crate) name: Option<Range<u16>>,
+}
+
+/// Keeps track of which message is on which row, so clicks can be mapped back to them
+///
+/// Messages are printed one line feed at a time and the terminal scrolls them
+/// up once they reach the bottom, so this does the same.
+#[derive(Default)]
+pub(crate) struct Layout {
+    rows: Vec<Option<Row>>,
+    cursor: usize,
+}
+
+impl Layout {
+    /// The screen was cleared and the cursor moved to the top
+    pub(crate) fn clear(&mut self, height: u16) {
+        self.rows.clear();
+        self.rows.resize(height as _, None);
+        self.cursor = 0;
+    }
+
+    /// A line feed, followed by printing `row`
+    pub(crate) fn line_feed(&mut self, row: Option<Row>) {
+        if self.rows.is_empty() {
+            return;
+        }
+
+        if self.cursor + 1 < self.rows.len() {
+            self.cursor += 1;
+        } else {
+            self.rows.remove(0);
+            self.rows.push(None);
+        }
+        self.rows[self.cursor] = row;
+    }
+
+    /// The bottom row was overwritten by the status line
+    pub(crate) fn status(&mut self) {
+        if let Some(row) = self.rows.last_mut() {
+            *row = None;
+        }
+    }
+
+    pub(crate) fn get(&self, row: u16) -> Option<&Row> {
+        self.rows.get(row as usize)?.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(id: usize) -> Option<Row> {
+        Some(Row { id, name: None })
+    }
+
+    #[test]
+    fn scrolls() {
+        let mut layout = Layout::default();
+        layout.clear(3);
+        layout.line_feed(row(0));
+        layout.line_feed(row(1));
+        // the first row is left blank
+        assert_eq!(layout.get(0), None);
+        assert_eq!(layout.get(2), row(1).as_ref());
+
+        layout.line_feed(row(2));
+        layout.line_feed(None);
+        assert_eq!(layout.get(0), row(1).as_ref());
+        assert_eq!(layout.get(1), row(2).as_ref());
+
+        layout.status();
+        assert_eq!(layout.get(2), None);
+        assert_eq!(layout.get(3), None);
+    }
+}
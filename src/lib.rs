@@ -1,4 +1,4 @@
-pub use args::Args;
+pub use args::{Args, Command};
 pub mod window;
 
 mod alt_screen;
@@ -8,15 +8,39 @@ mod app;
 pub use app::App;
 
 mod args;
+mod auth;
+mod clock;
+mod filter;
+mod frame;
+mod helix;
+mod pane;
+mod prompt;
 mod queue;
+mod ratelimit;
+mod reply;
+mod stats;
 mod testing;
+mod throttle;
 mod twitch;
+mod users;
 
-mod partition;
+mod clipboard;
+mod layout;
+mod link;
+mod moderate;
+mod overlay;
+mod terminal;
+mod timestamp;
 mod truncate;
+mod wrap;
 
 mod keys;
 
+mod transcript;
+
+mod export;
+pub use export::export;
+
 mod logger;
 pub use logger::Logger;
 
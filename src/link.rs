@@ -0,0 +1,156 @@
+/// A piece of a line of text
+#[derive(Debug, PartialEq)]
+pub(crate) enum Segment<'a> {
+    Text(&'a str),
+    /// (part of) a url, `url` is the entire thing
+    Link {
+        text: &'a str,
+        url: &'a str,
+    },
+}
+
+/// Finds the urls in the lines a message was wrapped into
+///
+/// Wrapping only ever adds or drops whitespace, so the words of each line are
+/// matched up with the words of the message. This way a url that was broken
+/// over several lines links to the whole url from each part.
+pub(crate) struct Linker<'a> {
+    words: std::str::SplitWhitespace<'a>,
+    // the word being matched, and how much of it is left
+    current: Option<(&'a str, usize)>,
+}
+
+impl<'a> Linker<'a> {
+    pub(crate) fn new(data: &'a str) -> Self {
+        Self {
+            words: data.split_whitespace(),
+            current: None,
+        }
+    }
+
+    /// Splits the next line into text and links. Lines must be given in order
+    pub(crate) fn line<'l>(&mut self, line: &'l str) -> Vec<Segment<'l>>
+    where
+        'a: 'l,
+    {
+        let mut segments = vec![];
+        // where the current run of plain text started
+        let mut text = 0;
+        let mut pos = 0;
+
+        while let Some(start) = line[pos..].find(|c: char| !c.is_whitespace()) {
+            let start = pos + start;
+            let end = line[start..]
+                .find(char::is_whitespace)
+                .map_or(line.len(), |end| start + end);
+            pos = end;
+
+            if let Some(url) = self.next_piece(end - start).and_then(url) {
+                if text < start {
+                    segments.push(Segment::Text(&line[text..start]));
+                }
+                segments.push(Segment::Link {
+                    text: &line[start..end],
+                    url,
+                });
+                text = end;
+            }
+        }
+
+        if text < line.len() {
+            segments.push(Segment::Text(&line[text..]));
+        }
+        segments
+    }
+
+    // the word that the next `len` bytes belong to
+    fn next_piece(&mut self, len: usize) -> Option<&'a str> {
+        let (word, left) = match self.current.take() {
+            Some(current) => current,
+            None => {
+                let word = self.words.next()?;
+                (word, word.len())
+            }
+        };
+
+        let left = left.saturating_sub(len);
+        if left > 0 {
+            self.current = Some((word, left));
+        }
+        Some(word)
+    }
+}
+
+/// The url in this word, without any trailing punctuation
+fn url(word: &str) -> Option<&str> {
+    let lower = word.get(..8)?.to_ascii_lowercase();
+    if !lower.starts_with("http://") && !lower.starts_with("https://") {
+        return None;
+    }
+    let url = word.trim_end_matches(&['.', ',', ';', ':', '!', '?', ')', '\'', '"'][..]);
+    Some(url).filter(|url| url.len() > "https://".len())
+}
+
+/// Wraps `text` in an OSC 8 hyperlink to `url`
+pub(crate) fn hyperlink(text: &str, url: &str) -> String {
+    format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn links() {
+        let data = "see https://example.com/a/long/path, or http://foo.bar ok";
+        let mut linker = Linker::new(data);
+
+        let url = "https://example.com/a/long/path";
+        assert_eq!(
+            linker.line("see https://ex"),
+            [
+                Segment::Text("see "),
+                Segment::Link {
+                    text: "https://ex",
+                    url
+                }
+            ]
+        );
+        assert_eq!(
+            linker.line("ample.com/a/l"),
+            [Segment::Link {
+                text: "ample.com/a/l",
+                url
+            }]
+        );
+        assert_eq!(
+            linker.line("ong/path, or"),
+            [
+                Segment::Link {
+                    text: "ong/path,",
+                    url
+                },
+                Segment::Text(" or"),
+            ]
+        );
+        assert_eq!(
+            linker.line("http://foo.bar ok"),
+            [
+                Segment::Link {
+                    text: "http://foo.bar",
+                    url: "http://foo.bar"
+                },
+                Segment::Text(" ok"),
+            ]
+        );
+    }
+
+    #[test]
+    fn not_links() {
+        let mut linker = Linker::new("http:// https is not a link");
+        assert_eq!(
+            linker.line("http:// https is not a link"),
+            [Segment::Text("http:// https is not a link")]
+        );
+    }
+}
@@ -27,6 +27,8 @@ impl Drop for Logger {
 }
 enum Target {
     File(File),
+    #[cfg(test)]
+    Memory(std::rc::Rc<std::cell::RefCell<Vec<String>>>),
     Noop,
 }
 
@@ -45,7 +47,6 @@ impl Logger {
 
         std::fs::OpenOptions::new()
             .append(true)
-            .write(true)
             .create(true)
             .open(dir.join(channel).with_extension("log"))
             .map(|fi| Self {
@@ -54,10 +55,25 @@ impl Logger {
             .map_err(Into::into)
     }
 
+    /// A logger that keeps the transcript in memory, so tests can look at it
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> (Self, std::rc::Rc<std::cell::RefCell<Vec<String>>>) {
+        let lines = std::rc::Rc::default();
+        let this = Self {
+            transcribe: Target::Memory(std::rc::Rc::clone(&lines)),
+        };
+        (this, lines)
+    }
+
     pub fn transcribe(&mut self, msg: &str) -> anyhow::Result<()> {
-        if let Target::File(file) = &mut self.transcribe {
-            writeln!(file, "{}", msg)?;
-            file.sync_all()?;
+        match &mut self.transcribe {
+            Target::File(file) => {
+                writeln!(file, "{}", msg)?;
+                file.sync_all()?;
+            }
+            #[cfg(test)]
+            Target::Memory(lines) => lines.borrow_mut().push(msg.to_string()),
+            Target::Noop => {}
         }
 
         Ok(())
@@ -0,0 +1,276 @@
+use anyhow::Context as _;
+use twitchchat::messages::Privmsg;
+
+/// How long slow mode makes chatters wait between messages, in seconds
+const SLOW: u64 = 30;
+
+/// What a moderator can do to a message's author, or to the channel
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Action {
+    /// delete just the message
+    Delete,
+    /// timeout the author, for this many seconds
+    Timeout(u32),
+    Ban,
+    Unban,
+    /// toggle slow mode
+    Slow,
+    /// toggle sub-only mode
+    SubsOnly,
+}
+
+impl Action {
+    /// The next action, for cycling through them
+    pub(crate) const fn next(self) -> Self {
+        match self {
+            Self::Delete => Self::Timeout(60),
+            Self::Timeout(60) => Self::Timeout(10 * 60),
+            Self::Timeout(600) => Self::Timeout(60 * 60),
+            Self::Timeout(..) => Self::Ban,
+            Self::Ban => Self::Unban,
+            Self::Unban => Self::Slow,
+            Self::Slow => Self::SubsOnly,
+            Self::SubsOnly => Self::Delete,
+        }
+    }
+
+    /// Whether this is done to the channel, rather than to a message
+    pub(crate) const fn is_channel_wide(self) -> bool {
+        matches!(self, Self::Slow | Self::SubsOnly)
+    }
+
+    /// What will be sent, and the question asked before sending it
+    ///
+    /// Only channel wide actions can be done without a target. The error says
+    /// why it can't be done, e.g. that the message has no id to delete it by.
+    pub(crate) fn pending(self, target: Option<&Target>, room: Room) -> anyhow::Result<Pending> {
+        let (question, request, done) = match (self, target) {
+            (Self::Slow, ..) if room.slow > 0 => (
+                "turn off slow mode?".into(),
+                Request::Slow(None),
+                "slow mode is off".into(),
+            ),
+            (Self::Slow, ..) => (
+                format!("turn on slow mode ({}s)?", SLOW),
+                Request::Slow(Some(SLOW)),
+                format!("slow mode is on ({}s)", SLOW),
+            ),
+            (Self::SubsOnly, ..) => (
+                format!("turn {} sub-only mode?", on_off(!room.subs_only)),
+                Request::SubsOnly(!room.subs_only),
+                format!("sub-only mode is {}", on_off(!room.subs_only)),
+            ),
+
+            (Self::Delete, Some(target)) => (
+                format!("delete {}'s message?", target.login),
+                Request::Delete(
+                    (target.id.clone()).context("that message can't be deleted, it has no id")?,
+                ),
+                format!("deleted {}'s message", target.login),
+            ),
+            (Self::Timeout(secs), Some(target)) => (
+                format!("timeout {} for {}?", target.login, duration(secs)),
+                Request::Ban(User::new(target), Some(secs)),
+                format!("timed out {} for {}", target.login, duration(secs)),
+            ),
+            (Self::Ban, Some(target)) => (
+                format!("ban {}?", target.login),
+                Request::Ban(User::new(target), None),
+                format!("banned {}", target.login),
+            ),
+            (Self::Unban, Some(target)) => (
+                format!("unban {}?", target.login),
+                Request::Unban(User::new(target)),
+                format!("unbanned {}", target.login),
+            ),
+            (.., None) => anyhow::bail!("{} needs a message, press its mark", self),
+        };
+        Ok(Pending {
+            question,
+            request,
+            broadcaster: room.id.context("the channel's id isn't known yet")?,
+            done,
+        })
+    }
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Delete => f.write_str("delete"),
+            Self::Timeout(secs) => write!(f, "timeout {}", duration(*secs)),
+            Self::Ban => f.write_str("ban"),
+            Self::Unban => f.write_str("unban"),
+            Self::Slow => f.write_str("slow mode"),
+            Self::SubsOnly => f.write_str("sub-only mode"),
+        }
+    }
+}
+
+/// Who (and which message) an action is done to
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Target {
+    pub(crate) login: String,
+    /// transcripts without tags don't have these
+    pub(crate) id: Option<String>,
+    pub(crate) user_id: Option<String>,
+}
+
+impl Target {
+    pub(crate) fn new(pm: &Privmsg<'_>) -> Self {
+        Self {
+            login: pm.name().to_string(),
+            id: pm.tags().get("id").map(ToString::to_string),
+            user_id: pm.tags().get("user-id").map(ToString::to_string),
+        }
+    }
+}
+
+/// An action waiting to be confirmed
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Pending {
+    pub(crate) question: String,
+    pub(crate) request: Request,
+    /// the channel's user id
+    pub(crate) broadcaster: u64,
+    /// what's said once it's done, e.g. `banned museun`
+    pub(crate) done: String,
+}
+
+/// What's asked of twitch's api, see [`crate::helix`]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Request {
+    /// delete the message with this id
+    Delete(String),
+    /// ban the user, or time them out for this many seconds
+    Ban(User, Option<u32>),
+    Unban(User),
+    /// turn slow mode on with this many seconds, or off
+    Slow(Option<u64>),
+    SubsOnly(bool),
+}
+
+/// A user, their id is looked up when it isn't known
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct User {
+    pub(crate) login: String,
+    pub(crate) id: Option<String>,
+}
+
+impl User {
+    fn new(target: &Target) -> Self {
+        Self {
+            login: target.login.clone(),
+            id: target.user_id.clone(),
+        }
+    }
+}
+
+/// The modes the channel is in, from its ROOMSTATE
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub(crate) struct Room {
+    /// the channel's user id, which the api wants rather than its name
+    pub(crate) id: Option<u64>,
+    /// how many seconds chatters wait between messages, 0 when it's off
+    pub(crate) slow: u64,
+    pub(crate) subs_only: bool,
+}
+
+const fn on_off(on: bool) -> &'static str {
+    match on {
+        true => "on",
+        false => "off",
+    }
+}
+
+pub(crate) fn duration(secs: u32) -> String {
+    match secs {
+        secs if secs % 3600 == 0 => format!("{}h", secs / 3600),
+        secs if secs % 60 == 0 => format!("{}m", secs / 60),
+        secs => format!("{}s", secs),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pending() {
+        let target = Target {
+            login: "museun".into(),
+            id: Some("abc".into()),
+            user_id: None,
+        };
+        let pending = |action: Action, room| {
+            let pending = action.pending(Some(&target), room).unwrap();
+            (pending.question, pending.request)
+        };
+        let room = Room {
+            id: Some(1),
+            ..Room::default()
+        };
+
+        assert_eq!(
+            pending(Action::Delete, room),
+            (
+                "delete museun's message?".into(),
+                Request::Delete("abc".into())
+            )
+        );
+        let user = User {
+            login: "museun".into(),
+            id: None,
+        };
+        assert_eq!(
+            pending(Action::Delete.next().next(), room),
+            (
+                "timeout museun for 10m?".into(),
+                Request::Ban(user, Some(600))
+            )
+        );
+        assert_eq!(
+            pending(Action::Slow, Room { slow: 30, ..room }),
+            ("turn off slow mode?".into(), Request::Slow(None))
+        );
+
+        // each reason it can't be done is shown
+        let error = |action: Action, target: Option<&Target>, room| {
+            action.pending(target, room).unwrap_err().to_string()
+        };
+
+        // only the channel can be acted on without a message
+        assert_eq!(
+            error(Action::Ban, None, room),
+            "ban needs a message, press its mark"
+        );
+        assert!(Action::SubsOnly.pending(None, room).is_ok());
+
+        // transcripts don't always have the message ids
+        let replayed = Target {
+            id: None,
+            ..target.clone()
+        };
+        assert_eq!(
+            error(Action::Delete, Some(&replayed), room),
+            "that message can't be deleted, it has no id"
+        );
+        assert!(Action::Ban.pending(Some(&replayed), room).is_ok());
+
+        // and the api needs to know which channel it is, which twitch says after joining
+        assert_eq!(
+            error(Action::SubsOnly, None, Room::default()),
+            "the channel's id isn't known yet"
+        );
+        assert_eq!(
+            error(Action::Delete, Some(&target), Room::default()),
+            "the channel's id isn't known yet"
+        );
+
+        let mut action = Action::Delete;
+        for _ in 0..8 {
+            action = action.next();
+        }
+        assert_eq!(action, Action::Delete);
+    }
+}
@@ -0,0 +1,207 @@
+use crossterm::style::*;
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::{frame::Canvas, truncate, window::Window, App};
+
+// the widest a popup gets, not counting its border
+const POPUP_WIDTH: usize = 50;
+// how many of a user's messages are shown
+const RECENT: usize = 10;
+
+/// Something shown over the chat, until escape is pressed
+///
+/// The chat keeps going underneath, and is drawn again once this is closed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Overlay {
+    /// takes up the whole screen
+    Whispers,
+    /// how active the chat has been, this is also full screen
+    Stats,
+    /// a popup about a user, from what has been seen of them
+    User { login: String, scroll: usize },
+}
+
+impl Overlay {
+    /// What's shown on the status line while this is open
+    pub(crate) fn status(&self) -> String {
+        match self {
+            Self::Whispers => "whispers: esc to close".to_string(),
+            Self::Stats => "stats: esc to close".to_string(),
+            Self::User { .. } => "f to filter by them, esc to close".to_string(),
+        }
+    }
+
+    /// Popups are drawn on top of the chat, rather than instead of it
+    pub(crate) const fn is_popup(&self) -> bool {
+        matches!(self, Self::User { .. })
+    }
+
+    pub(crate) fn scroll_up(&mut self) -> bool {
+        match self {
+            Self::User { scroll, .. } if *scroll > 0 => *scroll -= 1,
+            _ => return false,
+        }
+        true
+    }
+
+    pub(crate) fn scroll_down(&mut self) -> bool {
+        match self {
+            Self::User { scroll, .. } => *scroll += 1,
+            _ => return false,
+        }
+        true
+    }
+
+    fn title(&self) -> String {
+        match self {
+            Self::Whispers => "whispers".to_string(),
+            Self::Stats => "stats".to_string(),
+            Self::User { login, .. } => format!("@{}", login),
+        }
+    }
+
+    pub(crate) fn lines(&self, app: &App, window: &Window) -> Vec<String> {
+        match self {
+            Self::Whispers if app.whispers.is_empty() => vec!["no whispers yet".to_string()],
+            Self::Whispers => app
+                .whispers
+                .iter()
+                .map(|whisper| {
+                    let ts = whisper.ts.format("%X");
+                    format!("{} {}: {}", ts, whisper.from, whisper.text)
+                })
+                .collect(),
+            Self::Stats => app.stats.lines(),
+            Self::User { login, scroll } => {
+                let lines = user_lines(window, login);
+                // keep the last line on the screen
+                let scroll = (*scroll).min(lines.len().saturating_sub(1));
+                lines.into_iter().skip(scroll).collect()
+            }
+        }
+    }
+
+    pub(crate) fn print(
+        &self,
+        canvas: &mut Canvas,
+        lines: &[String],
+        (width, rows): (u16, usize),
+    ) -> anyhow::Result<()> {
+        match self.is_popup() {
+            true => print_popup(canvas, &self.title(), lines, (width, rows)),
+            false => print_list(canvas, &self.title(), lines, (width, rows)),
+        }
+    }
+}
+
+/// A whisper, these are kept apart from the chat
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Whisper {
+    pub(crate) ts: chrono::DateTime<chrono::Local>,
+    pub(crate) from: String,
+    pub(crate) text: String,
+}
+
+fn user_lines(window: &Window, login: &str) -> Vec<String> {
+    let user = window.users().get(login);
+    let mut lines = vec![];
+
+    // the newest message has the current name, color and badges
+    match user.and_then(|user| user.history.last()).map(|msg| &msg.pm) {
+        Some(pm) => {
+            match pm.display_name() {
+                Some(name) if !name.eq_ignore_ascii_case(pm.name()) => {
+                    lines.push(format!("name: {} ({})", name, pm.name()))
+                }
+                _ => lines.push(format!("name: {}", pm.name())),
+            }
+            if let Some(color) = pm.color() {
+                lines.push(format!("color: {}", color.rgb));
+            }
+            let badges = pm.tags().get("badges").filter(|s| !s.is_empty());
+            lines.push(format!(
+                "badges: {}",
+                badges.map_or_else(|| "none".to_string(), |s| s.replace(',', ", "))
+            ));
+        }
+        None => lines.push(format!("name: {}", login)),
+    }
+
+    let user = match user {
+        Some(user) => user,
+        None => return lines,
+    };
+    lines.push(format!("first seen: {}", user.first_seen.format("%X")));
+    lines.push(format!("last seen: {}", user.last_seen.format("%X")));
+    lines.push(format!("messages: {}", user.count));
+    if !user.moderation.is_empty() {
+        lines.push(String::new());
+        lines.push("moderation:".to_string());
+        for (ts, what) in &user.moderation {
+            lines.push(format!("{} {}", ts.format("%X"), what));
+        }
+    }
+
+    if !user.history.is_empty() {
+        lines.push(String::new());
+        lines.push("recent:".to_string());
+        for msg in user.history.iter().rev().take(RECENT) {
+            lines.push(format!("{} {}", msg.ts.format("%X"), msg.pm.data()));
+        }
+    }
+    lines
+}
+
+// the title on the top row, and as many of the newest lines as fit under it
+fn print_list(
+    canvas: &mut Canvas,
+    title: &str,
+    lines: &[String],
+    (width, rows): (u16, usize),
+) -> anyhow::Result<()> {
+    let title = style(title).attribute(Attribute::Bold);
+    crossterm::queue!(canvas.at(0, 0)?, Print(title))?;
+
+    let skip = lines.len().saturating_sub(rows.saturating_sub(1));
+    for (row, line) in lines.iter().skip(skip).enumerate() {
+        let line = truncate::truncate_or_pad(line, width as _);
+        crossterm::queue!(canvas.at(0, row as u16 + 1)?, Print(line.trim_end()))?;
+    }
+    Ok(())
+}
+
+// a box in the middle of the screen, with the title in its top border
+fn print_popup(
+    canvas: &mut Canvas,
+    title: &str,
+    lines: &[String],
+    (width, rows): (u16, usize),
+) -> anyhow::Result<()> {
+    // the border and a space on either side
+    let inner = (width as usize).saturating_sub(4).min(POPUP_WIDTH);
+    let height = lines.len().min(rows.saturating_sub(2));
+    let left = (width as usize).saturating_sub(inner + 4) / 2;
+    let top = rows.saturating_sub(height + 2) / 2;
+
+    let title = truncate::truncate_or_pad(title, inner.saturating_sub(2));
+    let title = title.trim_end();
+    let rule = "─".repeat((inner + 2).saturating_sub(title.width() + 3));
+    let mut row = top as u16;
+
+    crossterm::queue!(
+        canvas.at(left as u16, row)?,
+        Print("┌─ "),
+        Print(style(title).attribute(Attribute::Bold)),
+        Print(format!(" {}┐", rule))
+    )?;
+    for line in lines.iter().take(height) {
+        row += 1;
+        let line = truncate::truncate_or_pad(line, inner);
+        crossterm::queue!(canvas.at(left as u16, row)?, Print(format!("│ {} │", line)))?;
+    }
+    crossterm::queue!(
+        canvas.at(left as u16, row + 1)?,
+        Print(format!("└{}┘", "─".repeat(inner + 2)))
+    )?;
+    Ok(())
+}
@@ -0,0 +1,334 @@
+use crossterm::style::*;
+
+use crate::{filter::Filter, frame::Canvas, window::ViewMode};
+
+// TODO make this configurable
+const MAX_COLUMN_WIDTH: usize = 25;
+// TODO make this configurable
+pub(crate) const MIN_COLUMN_WIDTH: usize = 5;
+// TODO make this configurable
+const MIN_WINDOW_WIDTH: usize = 30;
+// the lines between the panes, dim so they don't compete with the chat
+const DIVIDER_COLOR: Color = Color::DarkGrey;
+// how much of the screen a new pane takes, by default
+const PANE_PERCENT: u16 = 40;
+
+/// A part of the screen that shows the chat its own way
+///
+/// The first pane is the chat itself, the others only show what their filter lets through.
+pub(crate) struct Pane {
+    /// the width of the name column
+    pub(crate) left: usize,
+    pub(crate) pad: String,
+    /// below this width the compact view is used
+    pub(crate) min: Option<usize>,
+    pub(crate) filter: Option<Filter>,
+    /// always use the compact view
+    pub(crate) compact: bool,
+}
+
+impl Pane {
+    pub(crate) fn new(left: usize, min: Option<usize>) -> Self {
+        Self {
+            left,
+            pad: " ".repeat(left),
+            min,
+            filter: None,
+            compact: false,
+        }
+    }
+
+    pub(crate) fn view_mode(&self, width: u16) -> ViewMode {
+        if self.compact || (width as usize) < self.min.unwrap_or(MIN_WINDOW_WIDTH) {
+            ViewMode::Compact
+        } else {
+            ViewMode::Normal
+        }
+    }
+
+    pub(crate) fn grow_nick_column(&mut self) -> bool {
+        if self.left == MAX_COLUMN_WIDTH {
+            return false;
+        }
+
+        self.left += 1;
+        // TODO this could just truncate or append spaces instead of using an entirely new allocation
+        self.pad = " ".repeat(self.left);
+        true
+    }
+
+    pub(crate) fn shrink_nick_column(&mut self) -> bool {
+        if self.left == MIN_COLUMN_WIDTH {
+            return false;
+        }
+
+        self.left -= 1;
+        // TODO this could just truncate or append spaces instead of using an entirely new allocation
+        self.pad = " ".repeat(self.left);
+        true
+    }
+}
+
+/// Which way a pane is split off from the rest of the screen
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    /// side by side
+    Right,
+    /// one above the other
+    Below,
+}
+
+/// A pane from the command line, e.g. `right,40%,mods` or `below,@museun,nick=8,compact`
+///
+/// This starts with where the pane goes, followed by any of its size, the width
+/// of its name column and `compact`. Anything else is its filter: `mods`,
+/// `highlights`, `@name`, or text to look for.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaneSpec {
+    pub(crate) direction: Direction,
+    pub(crate) percent: u16,
+    pub(crate) nick: Option<usize>,
+    pub(crate) compact: bool,
+    pub(crate) filter: Option<Filter>,
+}
+
+impl std::str::FromStr for PaneSpec {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(',').map(str::trim);
+        let direction = match parts.next() {
+            Some("right") => Direction::Right,
+            Some("below") => Direction::Below,
+            _ => anyhow::bail!("a pane starts with where it goes, right or below: '{}'", s),
+        };
+
+        let mut spec = Self {
+            direction,
+            percent: PANE_PERCENT,
+            nick: None,
+            compact: false,
+            filter: None,
+        };
+        for part in parts {
+            if let Some(percent) = part.strip_suffix('%') {
+                spec.percent = match percent.parse() {
+                    Ok(percent @ 10..=90) => percent,
+                    _ => anyhow::bail!("a pane's size has to be from 10% to 90%: '{}'", part),
+                };
+            } else if let Some(nick) = part.strip_prefix("nick=") {
+                let nick: usize = nick.parse()?;
+                spec.nick = Some(nick.clamp(MIN_COLUMN_WIDTH, MAX_COLUMN_WIDTH));
+            } else if part == "compact" {
+                spec.compact = true;
+            } else {
+                spec.filter = match part {
+                    "mods" => Some(Filter::Mods),
+                    "highlights" => Some(Filter::Highlights),
+                    part => Filter::parse(part),
+                };
+            }
+        }
+        Ok(spec)
+    }
+}
+
+/// Where something goes on the screen
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Rect {
+    pub(crate) x: u16,
+    pub(crate) y: u16,
+    pub(crate) width: u16,
+    pub(crate) height: u16,
+}
+
+impl Rect {
+    pub(crate) const fn contains(&self, column: u16, row: u16) -> bool {
+        column >= self.x
+            && column < self.x + self.width
+            && row >= self.y
+            && row < self.y + self.height
+    }
+}
+
+// which pane goes where, by its index
+type Panes = Vec<(usize, Rect)>;
+// the lines between the panes
+type Dividers = Vec<(Direction, Rect)>;
+
+/// How the screen is divided between the panes
+///
+/// New panes are split off from everything that's already there, so the chat
+/// is always in the top left corner.
+pub(crate) enum Split {
+    /// a pane, by its index
+    Pane(usize),
+    Split {
+        direction: Direction,
+        /// how much of the space the second one gets
+        percent: u16,
+        first: Box<Split>,
+        second: Box<Split>,
+    },
+}
+
+impl Split {
+    pub(crate) fn add(self, direction: Direction, percent: u16, pane: usize) -> Self {
+        Self::Split {
+            direction,
+            percent,
+            first: Box::new(self),
+            second: Box::new(Self::Pane(pane)),
+        }
+    }
+
+    pub(crate) const fn is_single(&self) -> bool {
+        matches!(self, Self::Pane(..))
+    }
+
+    /// Where each pane goes in `rect`, and the dividers between them
+    pub(crate) fn rects(&self, rect: Rect) -> (Panes, Dividers) {
+        let (mut panes, mut dividers) = (vec![], vec![]);
+        self.layout(rect, &mut panes, &mut dividers);
+        (panes, dividers)
+    }
+
+    fn layout(&self, rect: Rect, panes: &mut Panes, dividers: &mut Dividers) {
+        let (direction, percent, first, second) = match self {
+            Self::Pane(index) => return panes.push((*index, rect)),
+            Self::Split {
+                direction,
+                percent,
+                first,
+                second,
+            } => (*direction, *percent, first, second),
+        };
+
+        // the divider takes a column (or a row) out of the first one
+        let (a, divider, b) = match direction {
+            Direction::Right => {
+                let (a, b) = divide(rect.width, percent);
+                (
+                    Rect { width: a, ..rect },
+                    Rect {
+                        x: rect.x + a,
+                        width: a.min(1),
+                        ..rect
+                    },
+                    Rect {
+                        x: rect.x + rect.width - b,
+                        width: b,
+                        ..rect
+                    },
+                )
+            }
+            Direction::Below => {
+                let (a, b) = divide(rect.height, percent);
+                (
+                    Rect { height: a, ..rect },
+                    Rect {
+                        y: rect.y + a,
+                        height: a.min(1),
+                        ..rect
+                    },
+                    Rect {
+                        y: rect.y + rect.height - b,
+                        height: b,
+                        ..rect
+                    },
+                )
+            }
+        };
+
+        first.layout(a, panes, dividers);
+        dividers.push((direction, divider));
+        second.layout(b, panes, dividers);
+    }
+}
+
+// splits `total` into the first part and the second part, leaving one for the divider
+fn divide(total: u16, percent: u16) -> (u16, u16) {
+    let second = (u32::from(total) * u32::from(percent) / 100) as u16;
+    let first = total.saturating_sub(second).saturating_sub(1);
+    (first, second)
+}
+
+pub(crate) fn print_divider(
+    canvas: &mut Canvas,
+    direction: Direction,
+    rect: Rect,
+) -> anyhow::Result<()> {
+    match direction {
+        Direction::Right => {
+            for row in rect.y..rect.y + rect.height {
+                let line = style("│").with(DIVIDER_COLOR);
+                crossterm::queue!(canvas.at(rect.x, row)?, Print(line))?;
+            }
+        }
+        Direction::Below => {
+            let line = style("─".repeat(rect.width as _)).with(DIVIDER_COLOR);
+            crossterm::queue!(canvas.at(rect.x, rect.y)?, Print(line))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spec() {
+        let spec: PaneSpec = "right,30%,mods,nick=8,compact".parse().unwrap();
+        assert_eq!(
+            spec,
+            PaneSpec {
+                direction: Direction::Right,
+                percent: 30,
+                nick: Some(8),
+                compact: true,
+                filter: Some(Filter::Mods),
+            }
+        );
+
+        let spec: PaneSpec = "below,@Museun".parse().unwrap();
+        assert_eq!(spec.direction, Direction::Below);
+        assert_eq!(spec.percent, PANE_PERCENT);
+        assert_eq!(spec.filter, Some(Filter::Name("museun".into())));
+
+        assert!("left,mods".parse::<PaneSpec>().is_err());
+        assert!("right,5%".parse::<PaneSpec>().is_err());
+    }
+
+    #[test]
+    fn rects() {
+        let split = Split::Pane(0)
+            .add(Direction::Right, 40, 1)
+            .add(Direction::Below, 25, 2);
+        let rect = |x, y, width, height| Rect {
+            x,
+            y,
+            width,
+            height,
+        };
+
+        let (panes, dividers) = split.rects(rect(0, 0, 80, 20));
+        assert_eq!(
+            panes,
+            [
+                (0, rect(0, 0, 47, 14)),
+                (1, rect(48, 0, 32, 14)),
+                (2, rect(0, 15, 80, 5)),
+            ]
+        );
+        assert_eq!(
+            dividers,
+            [
+                (Direction::Right, rect(47, 0, 1, 14)),
+                (Direction::Below, rect(0, 14, 80, 1)),
+            ]
+        );
+        assert!(panes[0].1.contains(46, 13));
+        assert!(!panes[0].1.contains(47, 0));
+    }
+}
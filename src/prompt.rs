@@ -0,0 +1,36 @@
+/// What the text typed into the status line will be used for
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum PromptKind {
+    Search,
+    Filter,
+    /// how many of the newest messages to copy
+    CopyLast,
+    /// a message to send to the channel
+    Compose,
+}
+
+/// A single line of input shown on the status line
+#[derive(Clone, Debug)]
+pub(crate) struct Prompt {
+    pub(crate) kind: PromptKind,
+    pub(crate) input: String,
+}
+
+impl Prompt {
+    pub(crate) const fn new(kind: PromptKind) -> Self {
+        Self {
+            kind,
+            input: String::new(),
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let label = match self.kind {
+            PromptKind::Search => "/",
+            PromptKind::Filter => "filter: ",
+            PromptKind::CopyLast => "copy last: ",
+            PromptKind::Compose => "say: ",
+        };
+        format!("{}{}_", label, self.input)
+    }
+}
@@ -1,4 +1,10 @@
-use std::collections::{vec_deque::Iter, VecDeque};
+use std::collections::{
+    vec_deque::{Iter, IterMut},
+    VecDeque,
+};
+
+// the most that's allocated up front, larger queues grow as they fill up
+const PREALLOCATE_MAX: usize = 1024;
 
 pub struct Queue<T> {
     buf: VecDeque<T>,
@@ -9,7 +15,7 @@ pub struct Queue<T> {
 impl<T> Queue<T> {
     pub fn with_size(size: usize) -> Self {
         Self {
-            buf: VecDeque::with_capacity(size),
+            buf: VecDeque::with_capacity(size.min(PREALLOCATE_MAX)),
             size,
         }
     }
@@ -41,7 +47,11 @@ impl<T> Queue<T> {
         self.buf.back()
     }
 
-    pub fn iter(&self) -> Iter<T> {
+    pub fn iter(&self) -> Iter<'_, T> {
         self.buf.iter()
     }
+
+    pub fn iter_mut(&mut self) -> IterMut<'_, T> {
+        self.buf.iter_mut()
+    }
 }
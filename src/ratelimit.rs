@@ -0,0 +1,90 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+/// How often twitch lets a user send messages
+const WINDOW: Duration = Duration::from_secs(30);
+/// How many messages a user can send in the window
+const USER_MAX: usize = 20;
+/// Moderators (and the broadcaster) can send more in their channel
+const MODERATOR_MAX: usize = 100;
+
+/// A sliding window rate limit, going over it gets you locked out for a while
+pub(crate) struct RateLimit {
+    window: Duration,
+    max: usize,
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    pub(crate) fn new(max: usize, window: Duration) -> Self {
+        Self {
+            window,
+            max,
+            sent: VecDeque::with_capacity(max),
+        }
+    }
+
+    /// The limit for a user, or for a moderator of the channel
+    pub(crate) fn for_user(moderator: bool) -> Self {
+        let mut this = Self::new(USER_MAX, WINDOW);
+        this.set_moderator(moderator);
+        this
+    }
+
+    pub(crate) fn set_moderator(&mut self, moderator: bool) {
+        self.max = match moderator {
+            true => MODERATOR_MAX,
+            false => USER_MAX,
+        };
+    }
+
+    /// How long to wait until another message can be sent
+    pub(crate) fn wait_time(&mut self, now: Instant) -> Duration {
+        while let Some(&oldest) = self.sent.front() {
+            if now.duration_since(oldest) < self.window {
+                break;
+            }
+            self.sent.pop_front();
+        }
+
+        match self.sent.len().checked_sub(self.max) {
+            // the oldest message that keeps us at the limit has to leave the window
+            Some(over) => (self.sent[over] + self.window).saturating_duration_since(now),
+            None => Duration::ZERO,
+        }
+    }
+
+    pub(crate) fn record(&mut self, now: Instant) {
+        self.sent.push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_time() {
+        let start = Instant::now();
+        let secs = |n| start + Duration::from_secs(n);
+
+        let mut limit = RateLimit::new(2, Duration::from_secs(10));
+        assert_eq!(limit.wait_time(start), Duration::ZERO);
+        limit.record(start);
+        limit.record(secs(4));
+
+        assert_eq!(limit.wait_time(secs(5)), Duration::from_secs(5));
+        assert_eq!(limit.wait_time(secs(10)), Duration::ZERO);
+        limit.record(secs(10));
+        assert_eq!(limit.wait_time(secs(10)), Duration::from_secs(4));
+
+        // moderators can send more
+        let mut limit = RateLimit::for_user(false);
+        (0..20).for_each(|_| limit.record(start));
+        assert!(limit.wait_time(start) > Duration::ZERO);
+        limit.set_moderator(true);
+        assert_eq!(limit.wait_time(start), Duration::ZERO);
+    }
+}
@@ -0,0 +1,71 @@
+use twitchchat::messages::Privmsg;
+
+use crate::truncate;
+
+/// The message a reply was replying to, from its `reply-parent-*` tags
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Parent {
+    pub(crate) name: String,
+    pub(crate) body: String,
+}
+
+impl Parent {
+    pub(crate) fn new(pm: &Privmsg<'_>) -> Option<Self> {
+        let get = |key| tag(pm, key);
+        get("reply-parent-msg-id")?;
+        Some(Self {
+            name: get("reply-parent-display-name").or_else(|| get("reply-parent-user-login"))?,
+            body: get("reply-parent-msg-body").unwrap_or_default(),
+        })
+    }
+
+    /// `↪ replying to @user: first words…`, cut to fit in `width` columns
+    pub(crate) fn context(&self, width: usize) -> String {
+        let line = format!("↪ replying to @{}: {}", self.name, self.body);
+        truncate::truncate_or_pad(&line, width)
+            .trim_end()
+            .to_string()
+    }
+}
+
+/// The id of the message that started the thread this message is a part of
+///
+/// A message that isn't a reply starts its own thread.
+pub(crate) fn thread(pm: &Privmsg<'_>) -> Option<String> {
+    tag(pm, "reply-thread-parent-msg-id")
+        .or_else(|| tag(pm, "reply-parent-msg-id"))
+        .or_else(|| tag(pm, "id"))
+}
+
+fn tag(pm: &Privmsg<'_>, key: &str) -> Option<String> {
+    let value = pm.tags().get_unescaped(key)?;
+    Some(value.to_string()).filter(|s| !s.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transcript::parse_privmsg;
+
+    #[test]
+    fn parent() {
+        let pm = parse_privmsg(
+            "@id=2;reply-parent-msg-id=1;reply-parent-user-login=museun;reply-parent-display-name=Museun;\
+             reply-parent-msg-body=hello\\sthere\\sworld :someone!someone@someone PRIVMSG #museun :@Museun hi",
+        )
+        .unwrap();
+
+        let parent = Parent::new(&pm).unwrap();
+        assert_eq!(parent.body, "hello there world");
+        assert_eq!(
+            parent.context(80),
+            "↪ replying to @Museun: hello there world"
+        );
+        assert_eq!(parent.context(29), "↪ replying to @Museun: hello…");
+        assert_eq!(thread(&pm).as_deref(), Some("1"));
+
+        let pm = parse_privmsg("@id=1 :museun!museun@museun PRIVMSG #museun :hello").unwrap();
+        assert_eq!(Parent::new(&pm), None);
+        assert_eq!(thread(&pm).as_deref(), Some("1"));
+    }
+}
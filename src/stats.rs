@@ -0,0 +1,254 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use chrono::{DateTime, Local};
+use twitchchat::messages::Privmsg;
+
+use crate::Logger;
+
+// how many minutes the sparkline covers
+const MINUTES: i64 = 60;
+// how many of the top chatters and words are listed
+const TOP: usize = 5;
+// past this many words the least used are forgotten, down to WORDS_KEPT
+const WORDS_MAX: usize = 10_000;
+const WORDS_KEPT: usize = 8_000;
+
+const BARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// How active the chat has been this session
+///
+/// This is kept up to date as messages come in, rather than going over the
+/// chat buffer, which only has the newest messages.
+#[derive(Default)]
+pub(crate) struct Stats {
+    messages: usize,
+    // the number of messages in each minute, for the last hour of chat
+    minutes: VecDeque<(i64, usize)>,
+    // how many messages each login sent
+    chatters: HashMap<String, usize>,
+    // the chatters who chatted in this channel for the first time
+    first_timers: HashSet<String>,
+    // how many messages each word (or emote) was in
+    words: HashMap<String, usize>,
+}
+
+impl Stats {
+    pub(crate) fn record(&mut self, pm: &Privmsg<'_>, ts: DateTime<Local>) {
+        self.messages += 1;
+
+        let minute = ts.timestamp().div_euclid(60);
+        match self.minutes.iter_mut().rev().find(|(m, _)| *m <= minute) {
+            Some((m, count)) if *m == minute => *count += 1,
+            _ => {
+                let index = self.minutes.partition_point(|&(m, _)| m < minute);
+                self.minutes.insert(index, (minute, 1));
+            }
+        }
+        // only the last hour is kept
+        while let Some(&(newest, _)) = self.minutes.back() {
+            match self.minutes.front() {
+                Some(&(oldest, _)) if oldest <= newest - MINUTES => self.minutes.pop_front(),
+                _ => break,
+            };
+        }
+
+        let login = pm.name().to_lowercase();
+        if pm.tags().get("first-msg") == Some("1") {
+            self.first_timers.insert(login.clone());
+        }
+        *self.chatters.entry(login).or_default() += 1;
+
+        // a word repeated in a single message is only counted once
+        let words = pm.data().split_whitespace().collect::<HashSet<_>>();
+        for word in words {
+            *self.words.entry(word.to_string()).or_default() += 1;
+        }
+        if self.words.len() > WORDS_MAX {
+            self.forget_words();
+        }
+    }
+
+    // this only happens every few thousand new words, rather than on each one once it's full
+    fn forget_words(&mut self) {
+        let mut counts = (self.words.iter())
+            .map(|(word, &count)| (count, word.clone()))
+            .collect::<Vec<_>>();
+        let forget = counts.len().saturating_sub(WORDS_KEPT);
+        if forget == 0 {
+            return;
+        }
+        counts.select_nth_unstable(forget - 1);
+        for (_, word) in &counts[..forget] {
+            self.words.remove(word);
+        }
+    }
+
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.messages == 0
+    }
+
+    /// Messages per minute for the last hour of chat, oldest first
+    fn sparkline(&self) -> String {
+        let newest = match self.minutes.back() {
+            Some(&(newest, _)) => newest,
+            None => return String::new(),
+        };
+        let peak = self.peak().max(1);
+
+        (newest - MINUTES + 1..=newest)
+            .map(|minute| {
+                let count = self
+                    .minutes
+                    .iter()
+                    .find(|&&(m, _)| m == minute)
+                    .map_or(0, |&(_, count)| count);
+                match count {
+                    0 => ' ',
+                    n => BARS[(n * BARS.len() - 1) / peak],
+                }
+            })
+            .collect()
+    }
+
+    fn peak(&self) -> usize {
+        self.minutes
+            .iter()
+            .map(|&(_, count)| count)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// The stats as lines of text, for showing them or writing them to the transcript
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let chatters = self.chatters.len();
+        let first_timers = self.first_timers.len();
+        let percent = (first_timers * 100).checked_div(chatters).unwrap_or(0);
+
+        let mut lines = vec![
+            format!("messages per minute, the last hour (peak {}):", self.peak()),
+            self.sparkline(),
+            String::new(),
+            format!("messages: {}", self.messages),
+            format!("chatters: {}", chatters),
+            format!("first-time chatters: {} ({}%)", first_timers, percent),
+        ];
+
+        for (title, counts) in [
+            ("top chatters:", &self.chatters),
+            ("top words:", &self.words),
+        ] {
+            lines.push(String::new());
+            lines.push(title.to_string());
+            for (name, count) in top(counts) {
+                lines.push(format!("  {} {}", name, count));
+            }
+        }
+        lines
+    }
+
+    /// Writes the stats to the transcript, e.g. at the end of a session
+    pub(crate) fn transcribe(&self, logger: &mut Logger) -> anyhow::Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for line in self.lines().iter().filter(|line| !line.trim().is_empty()) {
+            logger.transcribe(&format!("*** stats: {}", line))?;
+        }
+        Ok(())
+    }
+}
+
+// the most common first, ties in alphabetical order
+fn top(counts: &HashMap<String, usize>) -> Vec<(&str, usize)> {
+    let mut top = counts
+        .iter()
+        .map(|(name, &count)| (&**name, count))
+        .collect::<Vec<_>>();
+    top.sort_by(|(a, i), (b, j)| j.cmp(i).then(a.cmp(b)));
+    top.truncate(TOP);
+    top
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    #[test]
+    fn record() {
+        let mut stats = Stats::default();
+        let messages = [
+            (
+                0,
+                "@first-msg=1 :museun!museun@museun PRIVMSG #museun :hello hello Kappa",
+            ),
+            (10, ":museun!museun@museun PRIVMSG #museun :Kappa"),
+            (
+                70,
+                ":shaken_bot!shaken_bot@shaken_bot PRIVMSG #museun :Kappa world",
+            ),
+            (70 + 58 * 60, ":someone!someone@someone PRIVMSG #museun :hi"),
+        ];
+        for (secs, line) in messages.iter() {
+            let pm = crate::transcript::parse_privmsg(line).unwrap();
+            stats.record(&pm, Local.timestamp_opt(*secs, 0).unwrap());
+        }
+
+        let mut sparkline = "█▄".to_string();
+        sparkline.push_str(&" ".repeat(57));
+        sparkline.push('▄');
+
+        let expected = [
+            "messages per minute, the last hour (peak 2):",
+            &*sparkline,
+            "",
+            "messages: 4",
+            "chatters: 3",
+            "first-time chatters: 1 (33%)",
+            "",
+            "top chatters:",
+            "  museun 2",
+            "  shaken_bot 1",
+            "  someone 1",
+            "",
+            "top words:",
+            "  Kappa 3",
+            "  hello 1",
+            "  hi 1",
+            "  world 1",
+        ];
+        assert_eq!(stats.lines(), expected);
+
+        // the first minute falls out of the last hour
+        let pm =
+            crate::transcript::parse_privmsg(":museun!museun@museun PRIVMSG #museun :hi").unwrap();
+        stats.record(&pm, Local.timestamp_opt(60 * 60, 0).unwrap());
+        assert_eq!(
+            stats.lines()[0],
+            "messages per minute, the last hour (peak 1):"
+        );
+
+        let (mut logger, transcript) = Logger::in_memory();
+        stats.transcribe(&mut logger).unwrap();
+        let transcript = transcript.borrow();
+        assert_eq!(
+            transcript[0],
+            "*** stats: messages per minute, the last hour (peak 1):"
+        );
+        assert!(transcript.contains(&"*** stats: messages: 5".to_string()));
+    }
+
+    #[test]
+    fn forget_words() {
+        let mut stats = Stats::default();
+        stats.words.insert("Kappa".into(), 2);
+        for i in 0..WORDS_MAX {
+            stats.words.insert(i.to_string(), 1);
+        }
+
+        // the least used go first, and then there's room for a while
+        stats.forget_words();
+        assert_eq!(stats.words.len(), WORDS_KEPT);
+        assert_eq!(stats.words.get("Kappa"), Some(&2));
+    }
+}
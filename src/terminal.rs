@@ -0,0 +1,369 @@
+use std::{io::Write, time::Duration};
+
+use crossterm::event::Event;
+
+/// Something the window can be rendered to, and input read from
+pub(crate) trait Terminal: Write {
+    /// The size of the terminal, in (columns, rows)
+    fn size(&self) -> anyhow::Result<(u16, u16)>;
+
+    /// Waits up to `timeout` for the next input event
+    fn poll(&mut self, timeout: Duration) -> anyhow::Result<Option<Event>>;
+}
+
+impl Terminal for std::io::Stdout {
+    fn size(&self) -> anyhow::Result<(u16, u16)> {
+        crossterm::terminal::size().map_err(Into::into)
+    }
+
+    fn poll(&mut self, timeout: Duration) -> anyhow::Result<Option<Event>> {
+        if !crossterm::event::poll(timeout)? {
+            return Ok(None);
+        }
+        crossterm::event::read().map(Some).map_err(Into::into)
+    }
+}
+
+#[cfg(test)]
+pub(crate) use screen::Screen;
+
+#[cfg(test)]
+mod screen {
+    use std::{
+        cell::{Cell, RefCell},
+        collections::VecDeque,
+        rc::Rc,
+        time::Duration,
+    };
+
+    use crossterm::event::Event;
+    use unicode_segmentation::UnicodeSegmentation as _;
+    use unicode_width::UnicodeWidthStr as _;
+
+    /// An in-memory terminal that interprets the escape sequences we emit
+    ///
+    /// Only the text is kept, styles (colors, reverse, etc) are ignored.
+    /// Clones share the same screen, so one can be handed to the `App` while
+    /// the test keeps the other. Input events are queued up with [`Screen::send`].
+    #[derive(Clone)]
+    pub(crate) struct Screen {
+        grid: Rc<RefCell<Grid>>,
+        events: Rc<RefCell<VecDeque<Event>>>,
+        written: Rc<Cell<usize>>,
+    }
+
+    impl Screen {
+        pub(crate) fn new(width: u16, height: u16) -> Self {
+            Self {
+                grid: Rc::new(RefCell::new(Grid::new(width, height))),
+                events: Rc::default(),
+                written: Rc::default(),
+            }
+        }
+
+        /// How many bytes have been written to the screen, to see how much drawing something costs
+        pub(crate) fn written(&self) -> usize {
+            self.written.get()
+        }
+
+        /// Queues up an event to be read by the next `poll`
+        pub(crate) fn send(&self, event: Event) {
+            self.events.borrow_mut().push_back(event)
+        }
+
+        /// Resizes the screen, clearing it, and queues up the resize event
+        pub(crate) fn resize(&self, width: u16, height: u16) {
+            self.grid.replace(Grid::new(width, height));
+            self.send(Event::Resize(width, height))
+        }
+
+        /// What was last copied to the clipboard, still base64 encoded
+        pub(crate) fn clipboard(&self) -> Option<String> {
+            self.grid.borrow().clipboard.clone()
+        }
+
+        /// The rows of the screen, with trailing whitespace removed
+        pub(crate) fn lines(&self) -> Vec<String> {
+            self.grid.borrow().lines()
+        }
+
+        /// The rows of the screen, with trailing empty rows removed
+        pub(crate) fn contents(&self) -> String {
+            let mut lines = self.lines();
+            while lines.last().filter(|s| s.is_empty()).is_some() {
+                lines.pop();
+            }
+            lines.join("\n")
+        }
+    }
+
+    struct Grid {
+        width: usize,
+        height: usize,
+        // blank cells are a space, the cell after a wide character is empty
+        cells: Vec<Vec<String>>,
+        col: usize,
+        row: usize,
+        // the cursor is past the last column, the next character wraps
+        pending_wrap: bool,
+        // the cell that was printed to last
+        last: Option<(usize, usize)>,
+        // bytes that didn't form a complete utf-8 sequence or escape yet
+        partial: Vec<u8>,
+        // the base64 data of the last OSC 52 copy
+        clipboard: Option<String>,
+    }
+
+    impl Grid {
+        fn new(width: u16, height: u16) -> Self {
+            let (width, height) = (width as usize, height as usize);
+            Self {
+                width,
+                height,
+                cells: vec![blank_row(width); height],
+                col: 0,
+                row: 0,
+                pending_wrap: false,
+                last: None,
+                partial: vec![],
+                clipboard: None,
+            }
+        }
+
+        fn lines(&self) -> Vec<String> {
+            self.cells
+                .iter()
+                .map(|row| row.concat().trim_end().to_string())
+                .collect()
+        }
+
+        fn feed(&mut self, input: &str) {
+            let mut iter = input.char_indices().peekable();
+            let mut text_start = None;
+
+            while let Some((i, ch)) = iter.next() {
+                if ch != '\x1b' && ch != '\n' && ch != '\r' {
+                    text_start.get_or_insert(i);
+                    continue;
+                }
+
+                if let Some(start) = text_start.take() {
+                    self.print(&input[start..i]);
+                }
+
+                match ch {
+                    '\n' => self.line_feed(),
+                    '\r' => self.move_to(0, self.row),
+                    _ => {
+                        let rest = &input[i + 1..];
+                        let len = self.escape(rest);
+                        for _ in 0..len {
+                            iter.next();
+                        }
+                    }
+                }
+            }
+
+            if let Some(start) = text_start {
+                self.print(&input[start..]);
+            }
+        }
+
+        // returns how many chars of the sequence were consumed
+        fn escape(&mut self, rest: &str) -> usize {
+            match rest.chars().next() {
+                Some('[') => {
+                    let end = match rest[1..].find(|c: char| c.is_ascii_alphabetic()) {
+                        Some(end) => end + 1,
+                        None => return rest.chars().count(),
+                    };
+                    let params = &rest[1..end];
+                    let cmd = rest[end..].chars().next().unwrap();
+                    self.csi(params, cmd);
+                    rest[..=end].chars().count()
+                }
+                // OSC, terminated by BEL or ST
+                Some(']') => {
+                    let (body, end) = rest
+                        .find('\x07')
+                        .map(|i| (i, i + 1))
+                        .or_else(|| rest.find("\x1b\\").map(|i| (i, i + 2)))
+                        .unwrap_or((rest.len(), rest.len()));
+                    if let Some(data) = rest[1..body].strip_prefix("52;c;") {
+                        self.clipboard = Some(data.to_string());
+                    }
+                    rest[..end].chars().count()
+                }
+                Some(..) => 1,
+                None => 0,
+            }
+        }
+
+        fn csi(&mut self, params: &str, cmd: char) {
+            // private modes (alt screen, cursor visibility, ..) don't matter here
+            if params.starts_with('?') {
+                return;
+            }
+
+            let mut args = params
+                .split(';')
+                .map(|s| s.parse::<usize>().unwrap_or_default());
+            let mut arg = |default: usize| args.next().filter(|&n| n != 0).unwrap_or(default);
+
+            match cmd {
+                'H' => {
+                    let row = arg(1);
+                    let col = arg(1);
+                    self.move_to(col - 1, row - 1)
+                }
+                'G' => self.move_to(arg(1) - 1, self.row),
+                'E' => self.move_to(0, self.row + arg(1)),
+                'F' => self.move_to(0, self.row.saturating_sub(arg(1))),
+                'A' => self.move_to(self.col, self.row.saturating_sub(arg(1))),
+                'B' => self.move_to(self.col, self.row + arg(1)),
+                'J' => {
+                    for row in &mut self.cells {
+                        *row = blank_row(self.width)
+                    }
+                }
+                'K' => self.cells[self.row] = blank_row(self.width),
+                _ => {}
+            }
+        }
+
+        fn move_to(&mut self, col: usize, row: usize) {
+            self.col = col.min(self.width.saturating_sub(1));
+            self.row = row.min(self.height.saturating_sub(1));
+            self.pending_wrap = false;
+        }
+
+        fn line_feed(&mut self) {
+            self.pending_wrap = false;
+            if self.row + 1 < self.height {
+                self.row += 1;
+                return;
+            }
+            self.cells.remove(0);
+            self.cells.push(blank_row(self.width));
+            self.last = self
+                .last
+                .and_then(|(row, col)| Some((row.checked_sub(1)?, col)));
+        }
+
+        fn print(&mut self, text: &str) {
+            for grapheme in text.graphemes(true) {
+                let width = grapheme.width();
+                if width == 0 {
+                    // zero width things (e.g. control characters) attach to the previous cell
+                    if let Some((row, col)) = self.last {
+                        self.cells[row][col].push_str(grapheme);
+                    }
+                    continue;
+                }
+
+                if self.pending_wrap || self.col + width > self.width {
+                    self.col = 0;
+                    self.line_feed();
+                }
+
+                self.cells[self.row][self.col] = grapheme.to_string();
+                self.last = Some((self.row, self.col));
+                for cell in 1..width {
+                    if let Some(cell) = self.cells[self.row].get_mut(self.col + cell) {
+                        cell.clear();
+                    }
+                }
+
+                self.col += width;
+                if self.col >= self.width {
+                    self.col = self.width - 1;
+                    self.pending_wrap = true;
+                }
+            }
+        }
+    }
+
+    impl std::io::Write for Screen {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.set(self.written.get() + buf.len());
+            self.grid.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Grid {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.partial.extend_from_slice(buf);
+
+            let valid = match std::str::from_utf8(&self.partial) {
+                Ok(s) => s.len(),
+                Err(err) => err.valid_up_to(),
+            };
+
+            // don't split an escape sequence between writes
+            let data = String::from_utf8(self.partial.drain(..valid).collect()).unwrap();
+            let end = match data.rfind('\x1b') {
+                Some(pos) if is_incomplete(&data[pos..]) => pos,
+                _ => data.len(),
+            };
+
+            self.feed(&data[..end]);
+            let mut rest = data.as_bytes()[end..].to_vec();
+            rest.append(&mut self.partial);
+            self.partial = rest;
+
+            Ok(buf.len())
+        }
+    }
+
+    fn blank_row(width: usize) -> Vec<String> {
+        vec![" ".to_string(); width]
+    }
+
+    fn is_incomplete(seq: &str) -> bool {
+        match seq.get(1..2) {
+            Some("[") => !seq[2..].contains(|c: char| c.is_ascii_alphabetic()),
+            Some("]") => !seq.contains('\x07') && !seq.contains("\x1b\\"),
+            Some(..) => false,
+            None => true,
+        }
+    }
+
+    impl super::Terminal for Screen {
+        fn size(&self) -> anyhow::Result<(u16, u16)> {
+            let grid = self.grid.borrow();
+            Ok((grid.width as u16, grid.height as u16))
+        }
+
+        fn poll(&mut self, timeout: Duration) -> anyhow::Result<Option<Event>> {
+            let event = self.events.borrow_mut().pop_front();
+            if event.is_none() {
+                // don't spin while waiting for messages
+                std::thread::sleep(timeout.min(Duration::from_millis(1)));
+            }
+            Ok(event)
+        }
+    }
+
+    mod tests {
+        use super::*;
+        use std::io::Write as _;
+
+        #[test]
+        fn screen() {
+            let mut screen = Screen::new(5, 3);
+            write!(screen, "hello world\n\x1b[0Gfoo\x1b[1;3Hx").unwrap();
+            assert_eq!(screen.lines(), vec![" wxrl", "d", "foo"]);
+
+            let mut screen = Screen::new(6, 2);
+            write!(screen, "日本語!").unwrap();
+            assert_eq!(screen.lines(), vec!["日本語", "!"]);
+
+            write!(screen, "\x1b[2J\x1b[1;1He\u{301}\x1b[31mx\x1b[0m").unwrap();
+            assert_eq!(screen.lines(), vec!["e\u{301}x", ""]);
+        }
+    }
+}
@@ -5,61 +5,166 @@ use std::{
     time::Duration,
 };
 
+use fastrand::Rng;
 use twitchchat::twitch::color::RGB;
 
+mod api;
+pub use api::make_fake_api;
+
+mod script;
+pub use script::Script;
+
+#[cfg(test)]
+mod harness;
+#[cfg(test)]
+pub(crate) use harness::Harness;
+
 const READY: &[&str] = &[
     ":tmi.twitch.tv CAP * ACK :twitch.tv/membership\r\n",
     ":tmi.twitch.tv CAP * ACK :twitch.tv/tags\r\n",
     ":tmi.twitch.tv CAP * ACK :twitch.tv/commands\r\n",
-    ":tmi.twitch.tv 001 justinfan1234 :Welcome, GLHF!\r\n",
-    ":tmi.twitch.tv 372 justinfan1234 :You are in a maze of twisty passages, all alike.\r\n",
-    ":tmi.twitch.tv 376 justinfan1234 :>\r\n",
+    ":tmi.twitch.tv 001 {nick} :Welcome, GLHF!\r\n",
+    ":tmi.twitch.tv 372 {nick} :You are in a maze of twisty passages, all alike.\r\n",
+    ":tmi.twitch.tv 376 {nick} :>\r\n",
 ];
 
-const JOIN: &str = ":justinfan1234!justinfan1234@justinfan1234.tmi.twitch.tv JOIN #testing\r\n";
-
-fn wait_for_join(mut io: &TcpStream) -> anyhow::Result<()> {
-    for line in READY {
-        io.write_all(line.as_bytes())?;
-    }
+// the channel's modes, which twitch sends right after joining
+const ROOMSTATE: &str = "@emote-only=0;followers-only=-1;r9k=0;room-id=1;slow=0;subs-only=0 \
+    :tmi.twitch.tv ROOMSTATE #testing\r\n";
+
+// what we are in the channel, the tests are moderators unless a script says otherwise
+const USERSTATE: &str = "@badges=moderator/1;color=#FF0000;display-name={nick};mod=1 \
+    :tmi.twitch.tv USERSTATE #testing\r\n";
+
+/// Greets the client with whatever nick it registered as, returning that nick
+fn wait_for_join(mut io: &TcpStream) -> anyhow::Result<String> {
+    let mut nick = String::new();
+    for line in BufReader::new(io).lines().map_while(Result::ok) {
+        if let Some(name) = line.strip_prefix("NICK ") {
+            nick = name.to_string();
+            for line in READY {
+                io.write_all(line.replace("{nick}", &nick).as_bytes())?;
+            }
+        }
 
-    for line in BufReader::new(io).lines().flatten() {
         if line == "JOIN #testing" {
-            io.write_all(JOIN.as_bytes())?;
+            write!(io, ":{0}!{0}@{0}.tmi.twitch.tv JOIN #testing\r\n", nick)?;
+            io.write_all(ROOMSTATE.as_bytes())?;
+            write!(io, "{}", USERSTATE.replace("{nick}", &nick))?;
             break;
         }
     }
 
-    Ok(())
+    Ok(nick)
 }
 
 fn garbage_out(
     io: &mut impl std::io::Write,
     chatters: &[Chatter],
     opts: &TestingOpts,
+    rng: &Rng,
 ) -> anyhow::Result<()> {
     let range = opts.duration_lower..opts.duration_upper;
-    while let Some(chatter) = chatters.choose() {
+    while let Some(chatter) = chatters.choose(rng) {
         write!(
             io,
-            "@color={color} :{name}!{name}@{name} PRIVMSG #testing :{msg}\r\n",
+            "@badges={badges};color={color};display-name={display};tmi-sent-ts={ts} :{name}!{name}@{name} PRIVMSG #testing :{msg}\r\n",
+            badges = chatter.badges,
             color = chatter.color,
+            display = chatter.display_name.as_deref().unwrap_or(&chatter.name),
             name = chatter.name,
-            msg = chatter.speak(opts)
+            msg = chatter.speak(opts, chatters, rng),
+            ts = sent_ts(),
         )?;
 
-        std::thread::sleep(Duration::from_millis(fastrand::u64(range.clone())));
+        std::thread::sleep(Duration::from_millis(rng.u64(range.clone())));
     }
     Ok(())
 }
 
-fn feed_chat(listener: TcpListener, chatters: Vec<Chatter>, opts: TestingOpts) {
+fn sent_ts() -> u128 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
+
+fn feed_chat(listener: TcpListener, chatters: Vec<Chatter>, opts: TestingOpts, rng: Rng) {
     for socket in listener.incoming().flatten() {
-        if let Err(..) = wait_for_join(&socket) {
+        if wait_for_join(&socket).is_err() {
             continue;
         }
 
-        if let Err(..) = garbage_out(&mut &socket, &chatters, &opts) {
+        if garbage_out(&mut &socket, &chatters, &opts, &rng).is_err() {
+            continue;
+        }
+    }
+}
+
+fn play_script(io: &TcpStream, nick: &str, script: &Script, pos: &mut usize) -> anyhow::Result<()> {
+    let mut writer = io;
+    while let Some((delay, event)) = script.events.get(*pos) {
+        std::thread::sleep(*delay);
+        *pos += 1;
+
+        match event {
+            script::Event::Send(line) => write!(writer, "{}\r\n", line)?,
+            script::Event::Reconnect => {
+                writer.write_all(b":tmi.twitch.tv RECONNECT\r\n")?;
+                return Ok(());
+            }
+            script::Event::Disconnect => return Ok(()),
+        }
+    }
+
+    // the script is done, but keep the connection open until the client leaves.
+    // messages are accepted with a USERSTATE, unless it's the same as the last one.
+    // twitch refuses both that and commands, which now go through its api
+    let mut last = None;
+    for (sent, line) in BufReader::new(io).lines().map_while(Result::ok).enumerate() {
+        let (tags, line) = match line.strip_prefix('@').and_then(|line| line.split_once(' ')) {
+            Some((tags, line)) => (tags, line),
+            None => ("", &*line),
+        };
+        let nonce = tags
+            .split(';')
+            .find_map(|tag| tag.strip_prefix("client-nonce="))
+            .unwrap_or_default();
+
+        if let Some(command) = line.strip_prefix("PRIVMSG #testing :/") {
+            let name = command.split_whitespace().next().unwrap_or_default();
+            write!(
+                writer,
+                "@msg-id=unrecognized_cmd :tmi.twitch.tv NOTICE #testing :Unrecognized command: /{}\r\n",
+                name
+            )?;
+        } else if let Some(text) = line.strip_prefix("PRIVMSG #testing :") {
+            if last.as_deref() == Some(text) {
+                writer.write_all(b"@msg-id=msg_duplicate :tmi.twitch.tv NOTICE #testing :Your message is identical to the one you sent less than 30 seconds ago.\r\n")?;
+                continue;
+            }
+            last = Some(text.to_string());
+            write!(
+                writer,
+                "@badges=moderator/1;client-nonce={};color=#FF0000;display-name={};id=sent-{};mod=1 \
+                :tmi.twitch.tv USERSTATE #testing\r\n",
+                nonce, nick, sent
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn feed_script(listener: TcpListener, script: Script) {
+    // this continues where the script left off when the client reconnects
+    let mut pos = 0;
+    for socket in listener.incoming().flatten() {
+        let nick = match wait_for_join(&socket) {
+            Ok(nick) => nick,
+            Err(..) => continue,
+        };
+
+        if play_script(&socket, &nick, &script, &mut pos).is_err() {
             continue;
         }
     }
@@ -72,6 +177,8 @@ pub struct TestingOpts {
     pub duration_upper: u64,
     pub length_lower: usize,
     pub length_upper: usize,
+    /// the same seed produces the same chatters and messages
+    pub seed: u64,
 }
 
 impl TestingOpts {
@@ -86,14 +193,17 @@ impl TestingOpts {
             duration_upper: get("READCHAT_DURATION_UPPER").unwrap_or(1500),
             length_lower: get("READCHAT_LENGTH_LOWER").unwrap_or(5),
             length_upper: get("READCHAT_LENGTH_UPPER").unwrap_or(300),
+            seed: get("READCHAT_SEED").unwrap_or_else(|| fastrand::u64(..)),
         }
     }
 }
 
 pub fn make_interesting_chat(opts: TestingOpts) -> anyhow::Result<std::net::SocketAddr> {
+    let rng = Rng::with_seed(opts.seed);
+
     let mut chatters = Vec::with_capacity(opts.unique_chatters);
     let mut seen = HashSet::new();
-    for chatter in std::iter::repeat_with(Chatter::new) {
+    for chatter in std::iter::repeat_with(|| Chatter::new(&rng)) {
         if seen.insert(chatter.name.clone()) {
             chatters.push(chatter);
         }
@@ -106,7 +216,16 @@ pub fn make_interesting_chat(opts: TestingOpts) -> anyhow::Result<std::net::Sock
     let listener = TcpListener::bind("localhost:0")?;
     let addr = listener.local_addr()?;
 
-    let _ = std::thread::spawn(move || feed_chat(listener, chatters, opts));
+    let _ = std::thread::spawn(move || feed_chat(listener, chatters, opts, rng));
+
+    Ok(addr)
+}
+
+pub fn make_scripted_chat(script: Script) -> anyhow::Result<std::net::SocketAddr> {
+    let listener = TcpListener::bind("localhost:0")?;
+    let addr = listener.local_addr()?;
+
+    let _ = std::thread::spawn(move || feed_script(listener, script));
 
     Ok(addr)
 }
@@ -114,46 +233,74 @@ pub fn make_interesting_chat(opts: TestingOpts) -> anyhow::Result<std::net::Sock
 #[derive(Debug)]
 struct Chatter {
     name: String,
+    display_name: Option<String>,
     color: RGB,
+    badges: &'static str,
 }
 
 impl Chatter {
-    fn new() -> Self {
+    fn new(rng: &Rng) -> Self {
         let mut name = format!(
             "{}{}",
-            ADJECTIVES.choose().unwrap(),
-            ANIMALS.choose().unwrap(),
+            ADJECTIVES.choose(rng).unwrap(),
+            ANIMALS.choose(rng).unwrap(),
         );
         name.push_str(
-            &std::iter::repeat_with(|| fastrand::u8(b'0'..b'9'))
-                .take(fastrand::usize(0..5))
+            &std::iter::repeat_with(|| rng.u8(b'0'..=b'8'))
+                .take(rng.usize(0..5))
                 .map(|c| c as char)
                 .collect::<String>(),
         );
 
+        // some people use a localized display name
+        let display_name =
+            (rng.usize(0..10) == 0).then(|| DISPLAY_NAMES.choose(rng).unwrap().to_string());
+
         let (_, color) = twitchchat::twitch::color::twitch_colors()
-            .choose()
+            .choose(rng)
             .copied()
             .unwrap();
 
-        Self { name, color }
+        let badges = BADGES.choose(rng).unwrap();
+
+        Self {
+            name,
+            display_name,
+            color,
+            badges,
+        }
     }
 
-    fn speak(&self, opts: &TestingOpts) -> String {
-        let mut len = fastrand::usize(opts.length_lower..opts.length_upper);
+    fn speak(&self, opts: &TestingOpts, chatters: &[Chatter], rng: &Rng) -> String {
+        let mut len = rng.usize(opts.length_lower..opts.length_upper);
         let mut data = String::new();
 
         let mut iter = IPSUM.iter().cycle();
         while len > 0 {
             let ipsum = iter.next().unwrap();
 
-            if fastrand::bool() {
+            if rng.bool() {
                 continue;
             }
 
-            data.push_str(ipsum);
+            // mostly ipsum, but sprinkle in the things that are hard to render
+            let mention;
+            let word = match rng.usize(0..24) {
+                0 => CJK.choose(rng).unwrap(),
+                1 => EMOJI.choose(rng).unwrap(),
+                2 => RTL.choose(rng).unwrap(),
+                3 => COMBINING.choose(rng).unwrap(),
+                4 => URLS.choose(rng).unwrap(),
+                5 => {
+                    mention = format!("@{}", chatters.choose(rng).unwrap().name);
+                    &*mention
+                }
+                _ => ipsum,
+            };
+
+            data.push_str(word);
             data.push(' ');
-            len = len.saturating_sub(ipsum.len() + 1)
+            len = len.saturating_sub(word.len() + 1)
         }
 
         data
@@ -162,16 +309,62 @@ impl Chatter {
 
 trait RandExt {
     type Output: ?Sized;
-    fn choose(&self) -> Option<&Self::Output>;
+    fn choose(&self, rng: &Rng) -> Option<&Self::Output>;
 }
 
 impl<T> RandExt for [T] {
     type Output = T;
-    fn choose(&self) -> Option<&Self::Output> {
-        self.get(fastrand::usize(0..self.len()))
+    fn choose(&self, rng: &Rng) -> Option<&Self::Output> {
+        if self.is_empty() {
+            return None;
+        }
+        self.get(rng.usize(0..self.len()))
     }
 }
 
+const BADGES: &[&str] = &[
+    "",
+    "",
+    "",
+    "subscriber/3",
+    "subscriber/12,premium/1",
+    "vip/1",
+    "moderator/1",
+    "moderator/1,subscriber/24",
+    "broadcaster/1,subscriber/0",
+];
+
+const DISPLAY_NAMES: &[&str] = &["日本語", "사용자", "小熊猫", "Ñandú", "Łoś"];
+
+const CJK: &[&str] = &[
+    "こんにちは",
+    "世界",
+    "日本語",
+    "テスト",
+    "中文",
+    "你好",
+    "한국어",
+    "안녕하세요",
+    "ｆｕｌｌｗｉｄｔｈ",
+];
+
+const EMOJI: &[&str] = &["👋🏽", "👩‍👩‍👧‍👦", "🏳️‍🌈", "👨‍💻", "🧑🏿‍🚀", "❤️", "🇯🇵", "😂😂😂", "🫠"];
+
+const RTL: &[&str] = &["مرحبا", "بالعالم", "שלום", "עולם", "سلام"];
+
+const COMBINING: &[&str] = &[
+    "e\u{301}",
+    "n\u{303}",
+    "a\u{30A}",
+    "Z\u{35A}\u{31E}a\u{36C}\u{360}l\u{353}g\u{31C}o",
+];
+
+const URLS: &[&str] = &[
+    "https://example.com",
+    "https://github.com/museun/readchat/issues?q=is%3Aopen+is%3Aissue+label%3Abug",
+    "https://www.twitch.tv/videos/123456789?t=1h2m3s",
+];
+
 const ADJECTIVES: &[&str] = &[
     "bad", "bald", "blue", "busy", "cool", "cute", "dark", "dead", "dull", "easy", "evil", "fair",
     "fine", "fit", "good", "hot", "hurt", "ill", "lazy", "odd", "open", "poor", "real", "rich",
@@ -198,3 +391,39 @@ static IPSUM: &[&str] = &[
     "eu", "fugiat", "nulla", "pariatur", "Excepteur", "sint", "occaecat", "cupidatat", "non", "proident", "sunt", "in",
     "culpa", "qui", "officia", "deserunt", "mollit", "anim", "id", "est", "laboru"
 ];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded() {
+        let opts = TestingOpts {
+            unique_chatters: 5,
+            duration_lower: 0,
+            duration_upper: 1,
+            length_lower: 5,
+            length_upper: 300,
+            seed: 42,
+        };
+
+        let generate = || {
+            let rng = Rng::with_seed(opts.seed);
+            let chatters = std::iter::repeat_with(|| Chatter::new(&rng))
+                .take(opts.unique_chatters)
+                .collect::<Vec<_>>();
+            std::iter::repeat_with(|| {
+                let chatter = chatters.choose(&rng).unwrap();
+                format!(
+                    "{}: {}",
+                    chatter.name,
+                    chatter.speak(&opts, &chatters, &rng)
+                )
+            })
+            .take(50)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(generate(), generate());
+    }
+}
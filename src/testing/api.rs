@@ -0,0 +1,169 @@
+use std::{
+    io::{BufRead as _, BufReader, Read as _, Write as _},
+    net::{TcpListener, TcpStream},
+};
+
+use serde_json::{json, Value};
+
+use crate::helix::Urls;
+
+/// A fake of twitch's api, enough of it for moderating
+///
+/// Like twitch, it wants a token, a client id and the ids of the broadcaster
+/// and moderator, and says what's missing otherwise.
+pub fn make_fake_api() -> anyhow::Result<Urls> {
+    let listener = TcpListener::bind("localhost:0")?;
+    let addr = listener.local_addr()?;
+
+    let _ = std::thread::spawn(move || {
+        for socket in listener.incoming().flatten() {
+            let _ = serve(&socket);
+        }
+    });
+
+    Ok(Urls {
+        api: format!("http://{}/helix", addr),
+        id: format!("http://{}/oauth2", addr),
+    })
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Value,
+}
+
+impl Request {
+    fn read(io: &TcpStream) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(io);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut parts = line.split_whitespace();
+        let (method, target) = match (parts.next(), parts.next()) {
+            (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+            _ => anyhow::bail!("not a request: {:?}", line),
+        };
+
+        let mut headers = vec![];
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line)?;
+            match line.trim_end().split_once(':') {
+                Some((key, value)) => {
+                    headers.push((key.to_ascii_lowercase(), value.trim().to_string()))
+                }
+                None => break,
+            }
+        }
+
+        let len = headers
+            .iter()
+            .find(|(key, _)| key == "content-length")
+            .and_then(|(_, len)| len.parse().ok())
+            .unwrap_or(0);
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body)?;
+
+        let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+        let query = query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        Ok(Self {
+            method,
+            path: path.to_string(),
+            query,
+            headers,
+            body: serde_json::from_slice(&body).unwrap_or(Value::Null),
+        })
+    }
+
+    fn header(&self, key: &str) -> Option<&str> {
+        (self.headers.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    fn query(&self, key: &str) -> Option<&str> {
+        (self.query.iter())
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| &**v)
+    }
+
+    fn require(&self, keys: &[&str]) -> Result<(), (u16, String)> {
+        match keys.iter().find(|key| self.query(key).is_none()) {
+            Some(key) => Err((400, format!("Missing required parameter \"{}\"", key))),
+            None => Ok(()),
+        }
+    }
+}
+
+fn serve(mut io: &TcpStream) -> anyhow::Result<()> {
+    let req = Request::read(io)?;
+    let (status, body) = match respond(&req) {
+        Ok(body) => (200, body),
+        Err((status, message)) => (status, json!({ "status": status, "message": message })),
+    };
+
+    let body = body.to_string();
+    write!(
+        io,
+        "HTTP/1.1 {} Fake\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    Ok(())
+}
+
+fn respond(req: &Request) -> Result<Value, (u16, String)> {
+    if req.path == "/oauth2/validate" {
+        return match req.header("authorization") {
+            Some(auth) if auth.starts_with("OAuth ") => Ok(json!({
+                "client_id": "fake",
+                "login": "shaken_bot",
+                "user_id": "2",
+            })),
+            _ => Err((401, "missing authorization token".into())),
+        };
+    }
+
+    let authorized = req
+        .header("authorization")
+        .is_some_and(|auth| auth.starts_with("Bearer "));
+    if !authorized || req.header("client-id").is_none() {
+        return Err((401, "OAuth token is missing".into()));
+    }
+
+    let moderate = ["broadcaster_id", "moderator_id"];
+    match (&*req.method, req.path.trim_start_matches("/helix/")) {
+        ("GET", "users") => {
+            let login = req.query("login").unwrap_or_default();
+            Ok(json!({ "data": [{ "id": format!("{}", 1000 + login.len()), "login": login }] }))
+        }
+        ("POST", "moderation/bans") => {
+            req.require(&moderate)?;
+            match req.body["data"]["user_id"].as_str() {
+                Some(..) => Ok(json!({ "data": [req.body["data"]] })),
+                None => Err((400, "Missing required parameter \"user_id\"".into())),
+            }
+        }
+        ("DELETE", "moderation/bans") => {
+            req.require(&[moderate[0], moderate[1], "user_id"])?;
+            Ok(Value::Null)
+        }
+        ("DELETE", "moderation/chat") => {
+            req.require(&moderate)?;
+            Ok(Value::Null)
+        }
+        ("PATCH", "chat/settings") => {
+            req.require(&moderate)?;
+            Ok(json!({ "data": [req.body] }))
+        }
+        _ => Err((404, "Not Found".into())),
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    cell::RefCell,
+    net::TcpStream,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::{Event, KeyEvent, KeyModifiers, MouseEvent, MouseEventKind};
+use flume::Receiver;
+use twitchchat::messages::Privmsg;
+
+use super::{make_fake_api, make_scripted_chat, Script};
+use crate::{
+    clock::Clock, keys::Message, terminal::Screen, twitch, window::Window, App, Args, Logger,
+};
+
+/// How long to wait for the app to reach an expected state
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs an `App` against the fake server, rendering to a [`Screen`]
+///
+/// The server plays back a [`Script`] while the test feeds the app input and
+/// looks at what ends up on the screen and in the transcript.
+pub(crate) struct Harness {
+    app: App,
+    screen: Screen,
+    messages: Receiver<Privmsg<'static>>,
+    logger: Logger,
+    transcript: Rc<RefCell<Vec<String>>>,
+    clock: Clock,
+    running: bool,
+}
+
+impl Harness {
+    pub(crate) fn new(args: Args, (width, height): (u16, u16), script: &str) -> Self {
+        let script = Script::parse(script).expect("valid script");
+        let addr = make_scripted_chat(script).unwrap();
+        let conn = TcpStream::connect(addr).unwrap();
+        let connection = twitch::spawn(
+            args.channel.clone(),
+            args.auth.clone(),
+            make_fake_api().unwrap(),
+            addr.to_string(),
+            conn,
+            std::io::sink(),
+        );
+
+        let screen = Screen::new(width, height);
+        let window = Window::new(args.nick_max, args.buffer_max, args.min_width)
+            .with_collapse(args.collapse)
+            .with_panes(&args.panes);
+        let mut app = App::new(args, window, Box::new(screen.clone()));
+        app.outgoing = Some(connection.outgoing).filter(|_| app.args.auth.is_some());
+        app.moderation = connection.moderation;
+        app.notices = Some(connection.notices);
+        let clock = Clock::manual();
+        app.clock = clock.clone();
+        let (logger, transcript) = Logger::in_memory();
+
+        Self {
+            app,
+            screen,
+            messages: connection.messages,
+            logger,
+            transcript,
+            clock,
+            running: true,
+        }
+    }
+
+    /// Presses a key, it'll be handled on the next step
+    pub(crate) fn press(&mut self, key: KeyEvent) -> &mut Self {
+        self.screen.send(Event::Key(key));
+        self.step()
+    }
+
+    /// Uses the mouse at this column and row, it'll be handled on the next step
+    pub(crate) fn mouse(&mut self, kind: MouseEventKind, column: u16, row: u16) -> &mut Self {
+        self.screen.send(Event::Mouse(MouseEvent {
+            kind,
+            column,
+            row,
+            modifiers: KeyModifiers::NONE,
+        }));
+        self.step()
+    }
+
+    /// Sends a message directly to the app, skipping the key bindings
+    pub(crate) fn send(&mut self, msg: Message) -> &mut Self {
+        self.app.events.0.send(msg).unwrap();
+        self.step()
+    }
+
+    /// Moves the app's clock along, it doesn't move on its own
+    pub(crate) fn advance(&mut self, by: Duration) -> &mut Self {
+        self.clock.advance(by);
+        self.step()
+    }
+
+    pub(crate) fn resize(&mut self, width: u16, height: u16) -> &mut Self {
+        self.screen.resize(width, height);
+        self.step()
+    }
+
+    /// Runs the app until `cond` is true, panicking if it takes too long
+    pub(crate) fn wait_for(&mut self, cond: impl Fn(&Self) -> bool) -> &mut Self {
+        let start = Instant::now();
+        while !cond(self) {
+            assert!(self.running, "app stopped early:\n{}", self.screen());
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "timed out, the screen was:\n{}",
+                self.screen()
+            );
+            self.step();
+        }
+        self
+    }
+
+    /// Runs the app until it has transcribed `n` messages
+    pub(crate) fn wait_for_messages(&mut self, n: usize) -> &mut Self {
+        self.wait_for(|this| this.transcript().len() >= n)
+    }
+
+    /// Runs the app until it stops on its own
+    pub(crate) fn wait_for_shutdown(&mut self) -> &mut Self {
+        let start = Instant::now();
+        while self.running {
+            assert!(start.elapsed() < TIMEOUT, "the app didn't stop");
+            self.step();
+        }
+        self
+    }
+
+    pub(crate) fn is_running(&self) -> bool {
+        self.running
+    }
+
+    pub(crate) fn screen(&self) -> String {
+        self.screen.contents()
+    }
+
+    /// What was last copied to the clipboard, base64 encoded
+    pub(crate) fn clipboard(&self) -> Option<String> {
+        self.screen.clipboard()
+    }
+
+    /// The transcribed lines, without their timestamps
+    pub(crate) fn transcript(&self) -> Vec<String> {
+        self.raw_transcript()
+            .iter()
+            .map(|line| line.split_once(' ').map_or(&**line, |(_, rest)| rest))
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    pub(crate) fn raw_transcript(&self) -> Vec<String> {
+        self.transcript.borrow().clone()
+    }
+
+    fn step(&mut self) -> &mut Self {
+        if self.running {
+            self.running = self
+                .app
+                .step(Some(&self.messages), &mut self.logger)
+                .unwrap();
+        }
+        self
+    }
+}
@@ -0,0 +1,242 @@
+use std::{path::Path, time::Duration};
+
+use anyhow::Context as _;
+
+/// A timeline of events that the fake server plays back
+///
+/// Each line is `<delay> <event> [args..]` where the delay is in milliseconds,
+/// relative to the previous event. Blank lines and lines starting with `#` are
+/// ignored.
+///
+/// | event                         | description                                  |
+/// | ----------------------------- | -------------------------------------------- |
+/// | `msg [@tags] <name> <text..>` | a `PRIVMSG` from `name`, with optional tags  |
+/// | `sub <name> <months> [text]`  | a resubscription `USERNOTICE`                |
+/// | `raid <name> <viewers>`       | a raid `USERNOTICE`                          |
+/// | `clearchat [name]`            | clear a user's messages, or the entire chat  |
+/// | `clearmsg <id>`               | delete a single message by its `id` tag      |
+/// | `notice <msg-id> <text..>`    | a `NOTICE` from the server                   |
+/// | `whisper <name> <text..>`     | a `WHISPER` from `name`                      |
+/// | `raw <line..>`                | send this line as is                         |
+/// | `reconnect`                   | ask the client to reconnect                  |
+/// | `disconnect`                  | drop the connection                          |
+/// | `repeat <n> <event..>`        | play the event `n` times, `delay` apart      |
+#[derive(Debug, Default)]
+pub struct Script {
+    pub(super) events: Vec<(Duration, Event)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) enum Event {
+    Send(String),
+    Reconnect,
+    Disconnect,
+}
+
+impl Script {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let data = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read script: {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("invalid script: {}", path.display()))
+    }
+
+    pub fn parse(input: &str) -> anyhow::Result<Self> {
+        let mut events = vec![];
+
+        for (i, line) in input.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            parse_line(line, &mut events).with_context(|| format!("on line {}", i + 1))?;
+        }
+
+        Ok(Self { events })
+    }
+}
+
+fn parse_line(line: &str, events: &mut Vec<(Duration, Event)>) -> anyhow::Result<()> {
+    let (delay, rest) = split(line);
+    let delay = delay
+        .parse()
+        .map(Duration::from_millis)
+        .with_context(|| format!("invalid delay: '{}'", delay))?;
+
+    let (kind, args) = split(rest);
+    if kind == "repeat" {
+        let (count, rest) = split(args);
+        let count = count
+            .parse()
+            .with_context(|| format!("invalid count: '{}'", count))?;
+
+        let (kind, args) = split(rest);
+        let event = parse_event(kind, args)?;
+        events.extend(std::iter::repeat((delay, event)).take(count));
+        return Ok(());
+    }
+
+    events.push((delay, parse_event(kind, args)?));
+    Ok(())
+}
+
+fn parse_event(kind: &str, args: &str) -> anyhow::Result<Event> {
+    const CHANNEL: &str = "#testing";
+
+    let event = match kind {
+        "msg" => {
+            let (tags, args) = match args.strip_prefix('@') {
+                Some(args) => {
+                    let (tags, args) = split(args);
+                    (format!("@{} ", tags), args)
+                }
+                None => (String::new(), args),
+            };
+
+            let (name, text) = split(args);
+            anyhow::ensure!(!name.is_empty(), "a name is required");
+
+            Event::Send(format!(
+                "{tags}:{name}!{name}@{name}.tmi.twitch.tv PRIVMSG {channel} :{text}",
+                tags = tags,
+                name = name,
+                channel = CHANNEL,
+                text = text
+            ))
+        }
+
+        "sub" => {
+            let (name, rest) = split(args);
+            let (months, text) = split(rest);
+            let months: u64 = months
+                .parse()
+                .with_context(|| format!("invalid months: '{}'", months))?;
+
+            Event::Send(format!(
+                "@msg-id=resub;login={name};display-name={name};msg-param-cumulative-months={months};\
+                system-msg={name}\\ssubscribed\\sfor\\s{months}\\smonths! :tmi.twitch.tv USERNOTICE {channel} :{text}",
+                name = name,
+                months = months,
+                channel = CHANNEL,
+                text = text
+            ))
+        }
+
+        "raid" => {
+            let (name, viewers) = split(args);
+            let viewers: u64 = viewers
+                .parse()
+                .with_context(|| format!("invalid viewers: '{}'", viewers))?;
+
+            Event::Send(format!(
+                "@msg-id=raid;login={name};msg-param-displayName={name};msg-param-login={name};\
+                msg-param-viewerCount={viewers};system-msg={viewers}\\sraiders\\sfrom\\s{name}\\shave\\sjoined! \
+                :tmi.twitch.tv USERNOTICE {channel}",
+                name = name,
+                viewers = viewers,
+                channel = CHANNEL,
+            ))
+        }
+
+        "clearchat" if args.is_empty() => {
+            Event::Send(format!(":tmi.twitch.tv CLEARCHAT {}", CHANNEL))
+        }
+
+        "clearchat" => Event::Send(format!(":tmi.twitch.tv CLEARCHAT {} :{}", CHANNEL, args)),
+
+        "clearmsg" => {
+            anyhow::ensure!(!args.is_empty(), "a message id is required");
+            Event::Send(format!(
+                "@target-msg-id={} :tmi.twitch.tv CLEARMSG {} :",
+                args, CHANNEL
+            ))
+        }
+
+        "notice" => {
+            let (id, text) = split(args);
+            anyhow::ensure!(!id.is_empty(), "a msg-id is required");
+            Event::Send(format!(
+                "@msg-id={} :tmi.twitch.tv NOTICE {} :{}",
+                id, CHANNEL, text
+            ))
+        }
+
+        "whisper" => {
+            let (name, text) = split(args);
+            anyhow::ensure!(!name.is_empty(), "a name is required");
+            Event::Send(format!(
+                ":{name}!{name}@{name}.tmi.twitch.tv WHISPER readchat :{text}",
+                name = name,
+                text = text
+            ))
+        }
+
+        "raw" => {
+            anyhow::ensure!(!args.is_empty(), "a line is required");
+            Event::Send(args.to_string())
+        }
+
+        "reconnect" => Event::Reconnect,
+        "disconnect" => Event::Disconnect,
+
+        kind => anyhow::bail!("unknown event: '{}'", kind),
+    };
+
+    Ok(event)
+}
+
+fn split(input: &str) -> (&str, &str) {
+    let input = input.trim_start();
+    match input.split_once(char::is_whitespace) {
+        Some((head, tail)) => (head, tail.trim_start()),
+        None => (input, ""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let script = Script::parse(
+            "
+            # a comment
+            0 msg museun hello   world
+            100 msg @color=#FF0000;badges=moderator/1 somemod hi
+            5 repeat 3 msg spammer spam
+            0 clearchat
+            0 clearchat spammer
+            0 reconnect
+            0 disconnect
+            ",
+        )
+        .unwrap();
+
+        let expected = [
+            (0, ":museun!museun@museun.tmi.twitch.tv PRIVMSG #testing :hello   world"),
+            (100, "@color=#FF0000;badges=moderator/1 :somemod!somemod@somemod.tmi.twitch.tv PRIVMSG #testing :hi"),
+            (5, ":spammer!spammer@spammer.tmi.twitch.tv PRIVMSG #testing :spam"),
+            (5, ":spammer!spammer@spammer.tmi.twitch.tv PRIVMSG #testing :spam"),
+            (5, ":spammer!spammer@spammer.tmi.twitch.tv PRIVMSG #testing :spam"),
+            (0, ":tmi.twitch.tv CLEARCHAT #testing"),
+            (0, ":tmi.twitch.tv CLEARCHAT #testing :spammer"),
+        ];
+
+        for ((delay, event), (expected_delay, expected)) in script.events.iter().zip(&expected) {
+            assert_eq!(*delay, Duration::from_millis(*expected_delay));
+            assert_eq!(*event, Event::Send(expected.to_string()));
+        }
+
+        assert_eq!(script.events.len(), expected.len() + 2);
+        assert_eq!(script.events[expected.len()].1, Event::Reconnect);
+        assert_eq!(script.events[expected.len() + 1].1, Event::Disconnect);
+    }
+
+    #[test]
+    fn errors() {
+        for bad in &["msg foo", "0 dance", "0 sub foo bar", "0 repeat x msg a b"] {
+            assert!(Script::parse(bad).is_err(), "{}", bad);
+        }
+    }
+}
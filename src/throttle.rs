@@ -0,0 +1,201 @@
+use std::{collections::VecDeque, time::Instant};
+
+use chrono::{DateTime, Local};
+use twitchchat::messages::Privmsg;
+
+use crate::filter::Filter;
+
+/// What to do with new messages once the backlog is too long
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Sample {
+    /// only keep every nth message
+    Every(usize),
+    /// only keep messages from the broadcaster, moderators and vips, and highlighted messages
+    Priority,
+}
+
+impl std::str::FromStr for Sample {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "priority" => Ok(Self::Priority),
+            s => match s.parse() {
+                Ok(n) if n >= 2 => Ok(Self::Every(n)),
+                _ => anyhow::bail!(
+                    "unknown sampling '{}', expected a number (2 or more) or priority",
+                    s
+                ),
+            },
+        }
+    }
+}
+
+/// Holds back messages so they're shown at most `rate` a second
+///
+/// The messages that can't be shown yet wait in a backlog. Once that's longer
+/// than `backlog_max` new messages are sampled, if there's a [`Sample`]. With
+/// [`Sample::Priority`] those skip the backlog, and are shown before it.
+pub(crate) struct Throttle {
+    rate: f64,
+    backlog_max: usize,
+    sample: Option<Sample>,
+    backlog: VecDeque<(Privmsg<'static>, DateTime<Local>)>,
+    // priority messages that came in while the backlog was full, these go first
+    priority: VecDeque<(Privmsg<'static>, DateTime<Local>)>,
+    // how many messages can be released right now
+    budget: f64,
+    last: Instant,
+    // messages that came in while sampling, for every nth
+    sampled: usize,
+    skipped: usize,
+}
+
+impl Throttle {
+    pub(crate) fn new(rate: u32, backlog_max: usize, sample: Option<Sample>, now: Instant) -> Self {
+        let rate = f64::from(rate.max(1));
+        Self {
+            rate,
+            backlog_max,
+            sample,
+            backlog: VecDeque::new(),
+            priority: VecDeque::new(),
+            // the first messages are shown right away
+            budget: rate,
+            last: now,
+            sampled: 0,
+            skipped: 0,
+        }
+    }
+
+    pub(crate) fn push(&mut self, pm: Privmsg<'static>, received: DateTime<Local>) {
+        if self.backlog.len() < self.backlog_max {
+            self.sampled = 0;
+            self.backlog.push_back((pm, received));
+            return;
+        }
+
+        match self.sample {
+            Some(Sample::Every(n)) => {
+                self.sampled += 1;
+                match self.sampled % n == 0 {
+                    true => self.backlog.push_back((pm, received)),
+                    false => self.skipped += 1,
+                }
+            }
+            Some(Sample::Priority) => match is_priority(&pm) {
+                true => self.priority.push_back((pm, received)),
+                false => self.skipped += 1,
+            },
+            None => self.backlog.push_back((pm, received)),
+        }
+    }
+
+    /// The messages that can be shown by `now`, oldest first
+    pub(crate) fn release(&mut self, now: Instant) -> Vec<(Privmsg<'static>, DateTime<Local>)> {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now;
+        // at most a second's worth can be saved up
+        self.budget = (self.budget + elapsed * self.rate).min(self.rate);
+
+        let n = (self.budget as usize).min(self.behind());
+        self.budget -= n as f64;
+        let priority = n.min(self.priority.len());
+        (self.priority.drain(..priority))
+            .chain(self.backlog.drain(..n - priority))
+            .collect()
+    }
+
+    /// How many messages are waiting to be shown
+    pub(crate) fn behind(&self) -> usize {
+        self.priority.len() + self.backlog.len()
+    }
+
+    /// How many messages were left out by sampling
+    pub(crate) const fn skipped(&self) -> usize {
+        self.skipped
+    }
+}
+
+fn is_priority(pm: &Privmsg<'_>) -> bool {
+    Filter::Mods.matches(pm) || Filter::Highlights.matches(pm)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn pm(line: &str) -> Privmsg<'static> {
+        crate::transcript::parse_privmsg(line).unwrap()
+    }
+
+    fn release(throttle: &mut Throttle, now: Instant) -> Vec<String> {
+        (throttle.release(now).into_iter())
+            .map(|(pm, _)| pm.data().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn rate() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(2, 100, None, start);
+        for i in 0..5 {
+            let line = format!(":museun!museun@museun PRIVMSG #museun :{}", i);
+            throttle.push(pm(&line), Local::now());
+        }
+
+        assert_eq!(release(&mut throttle, start), ["0", "1"]);
+        assert_eq!(throttle.behind(), 3);
+        assert!(release(&mut throttle, start + Duration::from_millis(250)).is_empty());
+        assert_eq!(
+            release(&mut throttle, start + Duration::from_millis(500)),
+            ["2"]
+        );
+        // it doesn't save up more than a second's worth
+        assert_eq!(
+            release(&mut throttle, start + Duration::from_secs(10)),
+            ["3", "4"]
+        );
+        assert_eq!(throttle.behind(), 0);
+    }
+
+    #[test]
+    fn sample() {
+        let start = Instant::now();
+        let mut throttle = Throttle::new(1, 2, Some(Sample::Every(2)), start);
+        for i in 0..6 {
+            let line = format!(":museun!museun@museun PRIVMSG #museun :{}", i);
+            throttle.push(pm(&line), Local::now());
+        }
+        assert_eq!((throttle.behind(), throttle.skipped()), (4, 2));
+        let now = start + Duration::from_secs(1);
+        let released = (1..=4)
+            .flat_map(|i| release(&mut throttle, now + Duration::from_secs(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(released, ["0", "1", "3", "5"]);
+
+        let mut throttle = Throttle::new(1, 1, Some(Sample::Priority), start);
+        throttle.push(pm(":museun!museun@museun PRIVMSG #museun :a"), Local::now());
+        throttle.push(pm(":museun!museun@museun PRIVMSG #museun :b"), Local::now());
+        throttle.push(
+            pm("@badges=moderator/1 :museun!museun@museun PRIVMSG #museun :c"),
+            Local::now(),
+        );
+        throttle.push(
+            pm("@msg-id=highlighted-message :museun!museun@museun PRIVMSG #museun :d"),
+            Local::now(),
+        );
+        assert_eq!((throttle.behind(), throttle.skipped()), (3, 1));
+
+        // they're shown ahead of the backlog
+        let released = (1..=3)
+            .flat_map(|i| release(&mut throttle, now + Duration::from_secs(i)))
+            .collect::<Vec<_>>();
+        assert_eq!(released, ["c", "d", "a"]);
+
+        assert_eq!("priority".parse::<Sample>().unwrap(), Sample::Priority);
+        assert_eq!("3".parse::<Sample>().unwrap(), Sample::Every(3));
+        assert!("1".parse::<Sample>().is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local, NaiveDate, TimeZone as _, Utc,
+};
+use twitchchat::messages::Privmsg;
+use unicode_width::UnicodeWidthStr as _;
+
+use crate::truncate::{self, Align, Ellipsis};
+
+// wide enough for "59m ago"
+const RELATIVE_WIDTH: usize = 7;
+// wide enough for "+999ms"
+pub(crate) const LATENCY_WIDTH: usize = 6;
+
+/// When the server says the message was sent, from its `tmi-sent-ts` tag
+pub(crate) fn sent_at(pm: &Privmsg<'_>) -> Option<DateTime<Local>> {
+    let ms = pm.tmi_sent_ts()?;
+    Local.timestamp_millis_opt(ms as _).single()
+}
+
+/// Renders how long a message took to get here, e.g. `+120ms` or `+1.5s`
+pub(crate) fn latency(latency: Option<chrono::Duration>) -> String {
+    let latency = match latency {
+        // clocks aren't perfectly in sync, so this can be slightly negative
+        Some(latency) => latency.num_milliseconds().max(0),
+        None => return " ".repeat(LATENCY_WIDTH),
+    };
+
+    let latency = match latency {
+        0..=999 => format!("+{}ms", latency),
+        _ => format!("+{:.1}s", latency as f64 / 1000.0),
+    };
+    truncate::fit(&latency, LATENCY_WIDTH, Align::Right, Ellipsis::Tail)
+}
+
+/// How the timestamps of messages are shown
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Timestamp {
+    pub style: Style,
+    pub zone: Zone,
+}
+
+impl Default for Timestamp {
+    fn default() -> Self {
+        Self {
+            style: Style::Format("%X".to_string()),
+            zone: Zone::Local,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Style {
+    /// A `strftime` format
+    Format(String),
+    /// How long ago it was, e.g. `2m ago`
+    Relative,
+}
+
+impl std::str::FromStr for Style {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "relative" {
+            return Ok(Self::Relative);
+        }
+
+        // chrono panics when displaying an invalid format, so catch it here
+        if StrftimeItems::new(s).any(|item| item == Item::Error) {
+            anyhow::bail!("invalid timestamp format: '{}'", s)
+        }
+        Ok(Self::Format(s.to_string()))
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Zone {
+    Local,
+    Utc,
+}
+
+impl Timestamp {
+    /// Renders `ts`, relative timestamps are relative to `now`
+    pub(crate) fn render(&self, ts: &DateTime<Local>, now: &DateTime<Local>) -> String {
+        let format = match &self.style {
+            Style::Format(format) => format,
+            Style::Relative => return relative(*now - *ts),
+        };
+
+        match self.zone {
+            Zone::Local => ts.format(format).to_string(),
+            Zone::Utc => ts.with_timezone(&Utc).format(format).to_string(),
+        }
+    }
+
+    /// Renders `ts` padded (or truncated) to a fixed width, so they line up
+    pub(crate) fn column(&self, ts: &DateTime<Local>, now: &DateTime<Local>) -> String {
+        let (width, align) = match &self.style {
+            Style::Format(format) => (self.width(format), Align::Left),
+            Style::Relative => (RELATIVE_WIDTH, Align::Right),
+        };
+        truncate::fit(&self.render(ts, now), width, align, Ellipsis::Tail)
+    }
+
+    // the widest `format` gets, e.g. `%B` is wider in september than in may
+    fn width(&self, format: &str) -> usize {
+        thread_local! {
+            static WIDTHS: RefCell<HashMap<(String, Zone), usize>> = RefCell::default();
+        }
+
+        let key = (format.to_string(), self.zone);
+        if let Some(width) = WIDTHS.with(|widths| widths.borrow().get(&key).copied()) {
+            return width;
+        }
+
+        // every month and day of the week, with two digit days and both halves of the day
+        let samples = (1..=12)
+            .flat_map(|month| (22..=28).map(move |day| NaiveDate::from_ymd_opt(2021, month, day)))
+            .flatten()
+            .flat_map(|date| [date.and_hms_opt(11, 59, 59), date.and_hms_opt(23, 59, 59)])
+            .flatten()
+            .map(|naive| Local.from_utc_datetime(&naive));
+        let width = samples
+            .map(|ts| self.render(&ts, &ts).width())
+            .max()
+            .unwrap_or_default();
+
+        WIDTHS.with(|widths| widths.borrow_mut().insert(key, width));
+        width
+    }
+}
+
+fn relative(elapsed: chrono::Duration) -> String {
+    let secs = elapsed.num_seconds();
+    match secs {
+        _ if secs < 5 => "now".to_string(),
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        // 2021-04-05 13:14:15 UTC
+        let ts = Local.timestamp_opt(1_617_628_455, 0).unwrap();
+        let timestamp = Timestamp {
+            style: "%Y-%m-%d %H:%M".parse().unwrap(),
+            zone: Zone::Utc,
+        };
+        assert_eq!(timestamp.render(&ts, &ts), "2021-04-05 13:14");
+        assert_eq!(timestamp.column(&ts, &ts), "2021-04-05 13:14");
+
+        // the column fits the longest month, rather than this one
+        let timestamp = Timestamp {
+            style: "%B %d".parse().unwrap(),
+            zone: Zone::Utc,
+        };
+        assert_eq!(timestamp.column(&ts, &ts), "April 05    ");
+
+        assert!("%Q".parse::<Style>().is_err());
+    }
+
+    #[test]
+    fn sent_at() {
+        let pm = crate::transcript::parse_privmsg(
+            "@tmi-sent-ts=1617628455123 :museun!museun@museun PRIVMSG #museun :hello",
+        )
+        .unwrap();
+        assert_eq!(
+            super::sent_at(&pm).unwrap().timestamp_millis(),
+            1617628455123
+        );
+
+        let pm = crate::transcript::parse_privmsg(":museun!museun@museun PRIVMSG #museun :hello")
+            .unwrap();
+        assert!(super::sent_at(&pm).is_none());
+    }
+
+    #[test]
+    fn latency() {
+        let ms = |ms| Some(chrono::Duration::milliseconds(ms));
+        assert_eq!(super::latency(ms(42)), " +42ms");
+        assert_eq!(super::latency(ms(1500)), " +1.5s");
+        assert_eq!(super::latency(ms(-3)), "  +0ms");
+        assert_eq!(super::latency(None), "      ");
+    }
+
+    #[test]
+    fn relative() {
+        let timestamp = Timestamp {
+            style: Style::Relative,
+            zone: Zone::Local,
+        };
+
+        let now = Local::now();
+        let ago = |secs| timestamp.column(&(now - chrono::Duration::seconds(secs)), &now);
+        assert_eq!(ago(0), "    now");
+        assert_eq!(ago(42), "42s ago");
+        assert_eq!(ago(150), " 2m ago");
+        assert_eq!(ago(3 * 3600), " 3h ago");
+        assert_eq!(ago(2 * 86400), " 2d ago");
+    }
+}
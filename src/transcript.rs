@@ -0,0 +1,117 @@
+use std::{
+    io::{BufRead as _, BufReader},
+    path::Path,
+};
+
+use chrono::TimeZone as _;
+use twitchchat::{messages::Privmsg, FromIrcMessage as _, IntoOwned as _};
+
+/// How messages are written to the transcript
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// `{ts} {name}: {data}`
+    Plain,
+    /// `{ts} {raw irc line}`, this keeps the tags (colors, badges, etc)
+    Irc,
+}
+
+impl Format {
+    pub(crate) fn line(&self, ts: u64, pm: &Privmsg<'_>) -> String {
+        match self {
+            Self::Plain => format!("{} {}: {}", ts, pm.name(), pm.data()),
+            Self::Irc => format!("{} {}", ts, pm.raw().trim_end()),
+        }
+    }
+}
+
+/// A message read back from a transcript
+pub(crate) struct Entry {
+    pub(crate) ts: chrono::DateTime<chrono::Local>,
+    pub(crate) pm: Privmsg<'static>,
+}
+
+/// Load all of the messages from a transcript.
+///
+/// Lines that cannot be parsed (e.g. the session markers) are skipped.
+pub(crate) fn load(path: &Path, channel: &str) -> anyhow::Result<Vec<Entry>> {
+    let file = std::fs::File::open(path)?;
+    let mut entries = vec![];
+    for line in BufReader::new(file).lines() {
+        if let Some(entry) = parse_line(&line?, channel) {
+            entries.push(entry);
+        }
+    }
+    Ok(entries)
+}
+
+pub(crate) fn parse_line(line: &str, channel: &str) -> Option<Entry> {
+    let (ts, rest) = line.split_once(' ')?;
+    let ts = chrono::Local.timestamp_opt(ts.parse().ok()?, 0).single()?;
+
+    // a name can never start with either of these, so this has to be a raw line
+    let pm = if rest.starts_with('@') || rest.starts_with(':') {
+        parse_privmsg(rest)?
+    } else {
+        let (name, data) = rest.split_once(": ")?;
+        parse_privmsg(&format!(
+            ":{name}!{name}@{name}.tmi.twitch.tv PRIVMSG {channel} :{data}",
+            name = name,
+            channel = channel,
+            data = data
+        ))?
+    };
+
+    // raw lines know exactly when they were sent
+    let ts = crate::timestamp::sent_at(&pm).unwrap_or(ts);
+    Some(Entry { ts, pm })
+}
+
+pub(crate) fn parse_privmsg(line: &str) -> Option<Privmsg<'static>> {
+    let line = format!("{}\r\n", line.trim_end());
+    let (_, msg) = twitchchat::irc::parse_one(&line).ok()?;
+    Privmsg::from_irc(msg).ok().map(|pm| pm.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plain() {
+        let entry = parse_line("1617000000 museun: hello: world", "#museun").unwrap();
+        assert_eq!(entry.ts.timestamp(), 1617000000);
+        assert_eq!(entry.pm.name(), "museun");
+        assert_eq!(entry.pm.channel(), "#museun");
+        assert_eq!(entry.pm.data(), "hello: world");
+    }
+
+    #[test]
+    fn irc() {
+        let line = "1617000000 @color=#FF0000;display-name=Museun :museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :hello world";
+        let entry = parse_line(line, "#testing").unwrap();
+        assert_eq!(entry.pm.display_name(), Some("Museun"));
+        assert_eq!(entry.pm.channel(), "#museun");
+        assert_eq!(entry.pm.data(), "hello world");
+        assert!(entry.pm.color().is_some());
+
+        let line = "1617000000 @tmi-sent-ts=1617000000250 :museun!museun@museun.tmi.twitch.tv PRIVMSG #museun :hi";
+        let entry = parse_line(line, "#testing").unwrap();
+        assert_eq!(entry.ts.timestamp_millis(), 1617000000250);
+    }
+
+    #[test]
+    fn round_trip() {
+        let pm = parse_privmsg("@color=#00FF00 :foo!foo@foo PRIVMSG #bar :baz quux").unwrap();
+        for format in [Format::Plain, Format::Irc] {
+            let entry = parse_line(&format.line(1617000000, &pm), "#bar").unwrap();
+            assert_eq!(entry.pm.name(), "foo");
+            assert_eq!(entry.pm.data(), "baz quux");
+        }
+    }
+
+    #[test]
+    fn skips_markers() {
+        assert!(parse_line("*** session start: 1617000000", "#museun").is_none());
+        assert!(parse_line("", "#museun").is_none());
+    }
+}
@@ -1,16 +1,135 @@
 use unicode_segmentation::UnicodeSegmentation as _;
 use unicode_width::UnicodeWidthStr as _;
 
+/// Where the text goes when it is narrower than its column
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+impl std::str::FromStr for Align {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "left" => Ok(Self::Left),
+            "right" => Ok(Self::Right),
+            "center" => Ok(Self::Center),
+            s => anyhow::bail!("unknown alignment '{}', expected left, right or center", s),
+        }
+    }
+}
+
+/// Where the ellipsis goes when the text is wider than its column
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Ellipsis {
+    /// `somelongn…`
+    Tail,
+    /// `some…name`
+    Middle,
+}
+
+impl std::str::FromStr for Ellipsis {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "tail" => Ok(Self::Tail),
+            "middle" => Ok(Self::Middle),
+            s => anyhow::bail!("unknown ellipsis '{}', expected tail or middle", s),
+        }
+    }
+}
+
 pub fn truncate_or_pad(input: &str, max: usize) -> String {
-    if input.width() > max {
-        return input
-            .graphemes(true)
-            .take(max - 1)
-            .chain(std::iter::once("…"))
-            .collect();
+    fit(input, max, Align::Left, Ellipsis::Tail)
+}
+
+/// Truncates or pads `input` so its exactly `max` columns wide
+pub fn fit(input: &str, max: usize, align: Align, ellipsis: Ellipsis) -> String {
+    let input = match input.width() > max {
+        true => truncate(input, max, ellipsis),
+        false => input.to_string(),
+    };
+
+    // a wide character can leave the truncated string a column short
+    let pad = max.saturating_sub(input.width());
+    let (left, right) = match align {
+        Align::Left => (0, pad),
+        Align::Right => (pad, 0),
+        Align::Center => (pad / 2, pad - pad / 2),
+    };
+
+    let mut s = String::with_capacity(input.len() + pad);
+    s.extend(std::iter::repeat(' ').take(left));
+    s.push_str(&input);
+    s.extend(std::iter::repeat(' ').take(right));
+    s
+}
+
+fn truncate(input: &str, max: usize, ellipsis: Ellipsis) -> String {
+    if max == 0 {
+        return String::new();
     }
-    let mut s = String::with_capacity(max);
-    s.push_str(input);
-    s.extend(std::iter::repeat(' ').take(max - input.width()));
+
+    // room for the ellipsis
+    let budget = max - 1;
+    let (head, tail) = match ellipsis {
+        Ellipsis::Tail => (budget, 0),
+        Ellipsis::Middle => (budget - budget / 2, budget / 2),
+    };
+
+    let head = take_width(input.graphemes(true), head).collect::<String>();
+    let mut tail = take_width(input.graphemes(true).rev(), tail).collect::<Vec<_>>();
+    tail.reverse();
+
+    let mut s = head;
+    s.push('…');
+    s.extend(tail);
     s
 }
+
+fn take_width<'a>(
+    graphemes: impl Iterator<Item = &'a str>,
+    max: usize,
+) -> impl Iterator<Item = &'a str> {
+    let mut width = 0;
+    graphemes.take_while(move |grapheme| {
+        width += grapheme.width();
+        width <= max
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn align() {
+        assert_eq!(fit("abc", 7, Align::Left, Ellipsis::Tail), "abc    ");
+        assert_eq!(fit("abc", 7, Align::Right, Ellipsis::Tail), "    abc");
+        assert_eq!(fit("abc", 7, Align::Center, Ellipsis::Tail), "  abc  ");
+        assert_eq!(fit("abc", 3, Align::Right, Ellipsis::Tail), "abc");
+    }
+
+    #[test]
+    fn ellipsis() {
+        let name = "someone_with_a_long_name";
+        assert_eq!(fit(name, 10, Align::Left, Ellipsis::Tail), "someone_w…");
+        assert_eq!(fit(name, 10, Align::Left, Ellipsis::Middle), "someo…name");
+        assert_eq!(fit(name, 1, Align::Left, Ellipsis::Middle), "…");
+        assert_eq!(fit(name, 0, Align::Left, Ellipsis::Middle), "");
+    }
+
+    #[test]
+    fn wide() {
+        // the truncated name is padded out to the column
+        assert_eq!(fit("日本語です", 6, Align::Left, Ellipsis::Tail), "日本… ");
+        assert_eq!(
+            fit("日本語です", 6, Align::Right, Ellipsis::Middle),
+            " 日…す"
+        );
+    }
+}
@@ -1,57 +1,188 @@
-use std::{io::Write, net::TcpStream};
+use std::{
+    io::Write,
+    net::TcpStream,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
 use twitchchat::{
     commands::{self, Channel},
-    messages::{Commands, Privmsg},
+    messages::{Commands, Privmsg, UserState},
+    twitch::BadgeKind,
     FromIrcMessage as _,
 };
 
-use channel::Sender;
+use crate::{
+    auth::{self, Auth},
+    helix, moderate,
+    ratelimit::RateLimit,
+};
+
+use channel::{Receiver, Sender};
 use flume as channel;
 
 use crossterm::{cursor::*, style::*, terminal::*};
 
-pub(super) fn run_to_completion(
+// how often the writer checks whether its connection is gone
+const POLL: Duration = Duration::from_millis(100);
+
+enum Status {
+    Reconnect,
+    Done,
+}
+
+/// The connection to a channel, running in the background
+pub(crate) struct Connection {
+    pub(crate) messages: Receiver<Privmsg<'static>>,
+    /// Messages to send to the channel, these are dropped unless authenticated
+    pub(crate) outgoing: Sender<String>,
+    /// Moderation actions, these go through the api. Only set when authenticated
+    pub(crate) moderation: Option<Sender<moderate::Pending>>,
+    pub(crate) notices: Receiver<Notice>,
+}
+
+/// What the server tells us, besides the chat
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Notice {
+    /// the outcome of something we did, e.g. a ban, or why it didn't work
+    Text(String),
+    /// the channel was joined, after the progress was printed over the screen
+    Joined,
+    /// the channel's user id
+    Room(u64),
+    /// whether we can moderate the channel, sent when it changes
+    Moderator(bool),
+    /// the channel's slow mode, in seconds
+    Slow(u64),
+    SubsOnly(bool),
+    /// only authenticated users get these
+    Whisper {
+        from: String,
+        text: String,
+    },
+    /// a user was timed out or banned, or had a message deleted
+    Moderated {
+        login: String,
+        what: String,
+    },
+}
+
+/// Runs the connection on a background thread, reporting its progress to `out`
+///
+/// `addr` is looked up again for each reconnect, as twitch may be moving us to another host.
+/// Moderating goes through the api at `urls` instead.
+pub(super) fn spawn(
     channel: String,
-    messages: Sender<Privmsg<'static>>,
+    auth: Option<Auth>,
+    urls: helix::Urls,
+    addr: String,
     conn: TcpStream,
-) -> anyhow::Result<()> {
-    let conn = &conn;
+    out: impl Write + Send + 'static,
+) -> Connection {
+    let (sender, messages) = channel::bounded(64);
+    let (outgoing, to_send) = channel::unbounded();
+    let (notify, notices) = channel::unbounded();
+    let moderation = (auth.clone()).map(|auth| helix::spawn(urls, auth, notify.clone()));
+    let session = Session {
+        channel,
+        auth,
+        addr,
+        messages: sender,
+        to_send,
+        notify,
+    };
+    let _ = std::thread::spawn(move || {
+        let _ = run_to_completion(&session, conn, out);
+    });
+    Connection {
+        messages,
+        outgoing,
+        moderation,
+        notices,
+    }
+}
 
-    crossterm::execute!(
-        std::io::stdout(),
-        MoveTo(0, 0),
-        Print(style("connecting..").with(Color::Cyan)),
-        MoveToNextLine(1),
-    )?;
+/// What each connection to the channel uses, it outlives them when reconnecting
+struct Session {
+    channel: String,
+    auth: Option<Auth>,
+    addr: String,
+    messages: Sender<Privmsg<'static>>,
+    to_send: Receiver<String>,
+    notify: Sender<Notice>,
+}
 
-    let user_config = twitchchat::UserConfig::builder()
-        .anonymous()
-        .enable_all_capabilities()
-        .build()?;
+fn run_to_completion(session: &Session, conn: TcpStream, out: impl Write) -> anyhow::Result<()> {
+    // only the first connection reports its progress, the chat will be
+    // on the screen for any reconnects
+    let mut conn = conn;
+    let mut out = Some(out);
+    while let Status::Reconnect = run_connection(session, &conn, out.take())? {
+        conn = TcpStream::connect(&session.addr)?;
+    }
+
+    Ok(())
+}
+
+fn run_connection(
+    session: &Session,
+    conn: &TcpStream,
+    mut out: Option<impl Write>,
+) -> anyhow::Result<Status> {
+    let Session {
+        channel,
+        auth,
+        messages,
+        to_send,
+        notify,
+        ..
+    } = session;
+    let auth = auth.as_ref();
+
+    if let Some(out) = &mut out {
+        crossterm::execute!(
+            out,
+            MoveToColumn(0),
+            Print(style("connecting..").with(Color::Cyan)),
+            MoveToNextLine(1),
+        )?;
+    }
+
+    let user_config = match auth {
+        Some(auth) => twitchchat::UserConfig::builder()
+            .name(&auth.nick)
+            .token(&auth.token),
+        None => twitchchat::UserConfig::builder().anonymous(),
+    }
+    .enable_all_capabilities()
+    .build()?;
+    let nick = auth.map_or(auth::ANONYMOUS, |auth| &auth.nick);
 
     let mut decoder = twitchchat::Decoder::new(conn);
     let mut encoder = twitchchat::Encoder::new(conn);
     encoder.encode(commands::register(&user_config))?;
 
-    let mut out = std::io::stdout();
-
     // ensure its converted properly.
-    let channel = Channel::new(&channel).to_string();
-
-    replace_line(
-        &mut out,
-        format!(
-            "{}{}",
-            style("joining ").with(Color::Cyan),
-            style(&channel).with(Color::Green),
-        ),
-    )?;
+    let channel = Channel::new(channel).to_string();
+
+    if let Some(out) = &mut out {
+        replace_line(
+            out,
+            format!(
+                "{}{}",
+                style("joining ").with(Color::Cyan),
+                style(&channel).with(Color::Green),
+            ),
+        )?;
+    }
 
     // TODO timeout logic here
 
     // wait for ready
-    while let Some(msg) = decoder.next() {
+    for msg in decoder.by_ref() {
         let msg = Commands::from_irc(msg?)?;
         if let Commands::IrcReady(_) = msg {
             break;
@@ -62,49 +193,213 @@ pub(super) fn run_to_completion(
     encoder.encode(commands::join(&channel))?;
 
     // wait for join
-    while let Some(msg) = decoder.next() {
+    for msg in decoder.by_ref() {
         let msg = Commands::from_irc(msg?)?;
         if let Commands::Join(msg) = msg {
-            if msg.channel() == &*channel && msg.name() == "justinfan1234" {
-                replace_line(
-                    &mut out,
-                    format!(
-                        "{}{}",
-                        style("joined ").with(Color::Cyan),
-                        style(&channel).with(Color::Green),
-                    ),
-                )?;
+            if msg.channel() == &*channel && msg.name() == nick {
+                if let Some(out) = &mut out {
+                    replace_line(
+                        out,
+                        format!(
+                            "{}{}",
+                            style("joined ").with(Color::Cyan),
+                            style(&channel).with(Color::Green),
+                        ),
+                    )?;
+                    notify.send(Notice::Joined)?;
+                }
                 break;
             }
         }
     }
 
+    // messages are sent from their own thread, so they don't wait on the chat
+    let writer = Writer::default();
+    let (sent, pending) = channel::unbounded();
+    // what was sent but not confirmed yet, oldest first
+    let mut unconfirmed = vec![];
+    let handle = match auth {
+        Some(..) => {
+            let (writer, conn, channel) = (writer.clone(), conn.try_clone()?, channel.clone());
+            let to_send = to_send.clone();
+            Some(std::thread::spawn(move || {
+                writer.run(&channel, conn, &to_send, &sent)
+            }))
+        }
+        None => None,
+    };
+
     // and then run the main loop
+    let mut status = Status::Done;
     while let Some(Ok(msg)) = decoder.next() {
-        match Commands::from_irc(msg)? {
-            Commands::Ping(msg) => {
-                if encoder.encode(commands::pong(msg.token())).is_err() {
+        let ok = match Commands::from_irc(msg)? {
+            Commands::Ping(msg) => encoder.encode(commands::pong(msg.token())).is_ok(),
+            Commands::Privmsg(msg) => messages.send(msg).is_ok(),
+            Commands::Reconnect(_) => {
+                status = Status::Reconnect;
+                false
+            }
+
+            // twitch doesn't echo our own messages, but it confirms them with this
+            Commands::UserState(msg) if msg.channel() == channel => {
+                // the broadcaster isn't marked as a moderator, but can do as much
+                let moderator = msg.is_moderator()
+                    || (msg.badges().iter()).any(|badge| badge.kind == BadgeKind::Broadcaster);
+                let changed = writer.moderator.swap(moderator, Ordering::Relaxed) != moderator;
+                if changed && notify.send(Notice::Moderator(moderator)).is_err() {
                     break;
                 }
+
+                unconfirmed.extend(pending.try_iter());
+                match confirmed(&mut unconfirmed, msg.tags().get("client-nonce")) {
+                    Some(text) => {
+                        echo(&msg, nick, &text).map_or(true, |pm| messages.send(pm).is_ok())
+                    }
+                    None => true,
+                }
             }
-            Commands::Privmsg(msg) => {
-                if messages.send(msg).is_err() {
-                    break;
+            // and this is what happens when twitch refuses one
+            Commands::Notice(msg) if msg.channel() == channel => {
+                notify.send(Notice::Text(msg.message().to_string())).is_ok()
+            }
+
+            Commands::ClearChat(msg) if msg.channel() == channel => match msg.name() {
+                Some(login) => {
+                    let what = match msg.ban_duration() {
+                        Some(secs) => format!("timed out for {}", moderate::duration(secs as _)),
+                        None => "banned".to_string(),
+                    };
+                    let login = login.to_string();
+                    notify.send(Notice::Moderated { login, what }).is_ok()
+                }
+                None => notify
+                    .send(Notice::Text("the chat was cleared".into()))
+                    .is_ok(),
+            },
+            Commands::ClearMsg(msg) if msg.channel() == channel => match msg.login() {
+                Some(login) => {
+                    let what = format!("message deleted: {}", msg.message().unwrap_or_default());
+                    let login = login.to_string();
+                    notify.send(Notice::Moderated { login, what }).is_ok()
                 }
+                None => true,
+            },
+
+            // subs, raids and the like
+            Commands::UserNotice(msg) if msg.channel() == channel => match msg.system_msg() {
+                Some(text) => notify.send(Notice::Text(text)).is_ok(),
+                None => true,
+            },
+
+            Commands::Whisper(msg) => {
+                let from = msg.display_name().unwrap_or_else(|| msg.name());
+                let (from, text) = (from.to_string(), msg.data().to_string());
+                notify.send(Notice::Whisper { from, text }).is_ok()
+            }
+
+            // after joining this has every mode, after that only the one that changed
+            Commands::RoomState(msg) if msg.channel() == channel => {
+                let tags = msg.tags();
+                let room = tags.get_parsed("room-id").map(Notice::Room);
+                let slow = tags.get("slow").map(|_| msg.is_slow_mode().unwrap_or(0));
+                let subs_only = tags.get("subs-only").map(|_| msg.is_subs_only());
+                let mut notices = (room.into_iter())
+                    .chain(slow.map(Notice::Slow))
+                    .chain(subs_only.map(Notice::SubsOnly));
+                notices.all(|notice| notify.send(notice).is_ok())
             }
 
-            // TODO handle Reconnect
-            _ => {}
+            _ => true,
+        };
+
+        if !ok {
+            break;
         }
 
-        // Commands::ClearChat(_) => {}
-        // Commands::ClearMsg(_) => {}
         // Commands::HostTarget(_) => {}
-        // Commands::Notice(_) => {}
-        // Commands::UserNotice(_) => {}
     }
 
-    Ok(())
+    writer.stop.store(true, Ordering::Relaxed);
+    if let Some(handle) = handle {
+        let _ = handle.join();
+    }
+
+    Ok(status)
+}
+
+/// Sends the messages typed into the compose line, within the rate limit
+#[derive(Clone, Default)]
+struct Writer {
+    moderator: Arc<AtomicBool>,
+    stop: Arc<AtomicBool>,
+}
+
+impl Writer {
+    fn run(
+        &self,
+        channel: &str,
+        conn: TcpStream,
+        to_send: &Receiver<String>,
+        sent: &Sender<(String, String)>,
+    ) -> anyhow::Result<()> {
+        let mut encoder = twitchchat::Encoder::new(conn);
+        let mut limit = RateLimit::for_user(false);
+
+        while !self.stop.load(Ordering::Relaxed) {
+            let text = match to_send.recv_timeout(POLL) {
+                Ok(text) => text,
+                Err(channel::RecvTimeoutError::Timeout) => continue,
+                Err(channel::RecvTimeoutError::Disconnected) => break,
+            };
+
+            limit.set_moderator(self.moderator.load(Ordering::Relaxed));
+            std::thread::sleep(limit.wait_time(Instant::now()));
+            limit.record(Instant::now());
+
+            // the server confirms it with a USERSTATE carrying the same nonce, which is
+            // when it's shown. commands (e.g. a ban) are answered with a NOTICE instead
+            let nonce = format!("{:016x}", fastrand::u64(..));
+            if !text.starts_with('/') {
+                sent.send((nonce.clone(), text.clone()))?;
+            }
+            encoder.encode(commands::raw(&format!(
+                "@client-nonce={} PRIVMSG {} :{}",
+                nonce, channel, text
+            )))?;
+        }
+        Ok(())
+    }
+}
+
+// the text of the message this nonce belongs to. anything sent before it was
+// refused (or never confirmed), so it's dropped
+fn confirmed(unconfirmed: &mut Vec<(String, String)>, nonce: Option<&str>) -> Option<String> {
+    let index = unconfirmed
+        .iter()
+        .position(|(sent, _)| Some(&**sent) == nonce)?;
+    unconfirmed
+        .drain(..=index)
+        .next_back()
+        .map(|(_, text)| text)
+}
+
+/// The message we sent, dressed up with our badges and color from the USERSTATE
+///
+/// This also has its `id`, so it can be replied to or deleted like any other.
+fn echo(state: &UserState<'_>, nick: &str, text: &str) -> Option<Privmsg<'static>> {
+    // `raw_tags` is the whole line, so they're put back together
+    let mut tags = String::new();
+    for (key, value) in state.tags().iter() {
+        tags.push_str(&format!("{}={};", key, value));
+    }
+    crate::transcript::parse_privmsg(&format!(
+        "@{tags}tmi-sent-ts={ts} :{nick}!{nick}@{nick}.tmi.twitch.tv PRIVMSG {channel} :{text}",
+        tags = tags,
+        ts = chrono::Utc::now().timestamp_millis(),
+        nick = nick,
+        channel = state.channel(),
+        text = text,
+    ))
 }
 
 fn replace_line(w: &mut impl Write, line: impl ToString) -> anyhow::Result<()> {
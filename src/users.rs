@@ -0,0 +1,237 @@
+use std::collections::{BTreeSet, HashMap};
+
+use chrono::{DateTime, Local};
+use twitchchat::messages::Privmsg;
+
+use crate::{queue::Queue, window::Message};
+
+// how many users are remembered, the ones seen longest ago are forgotten first
+const USERS_MAX: usize = 5_000;
+// how many messages are kept for each of them
+const HISTORY_MAX: usize = 50;
+
+/// What readchat has seen of a user this session
+pub(crate) struct User {
+    pub(crate) login: String,
+    pub(crate) first_seen: DateTime<Local>,
+    pub(crate) last_seen: DateTime<Local>,
+    pub(crate) count: usize,
+    /// timeouts, bans and deleted messages, oldest first
+    pub(crate) moderation: Vec<(DateTime<Local>, String)>,
+    /// their newest messages, oldest first. these outlive the chat buffer
+    pub(crate) history: Queue<Message<'static>>,
+    // the lowercased names they went by, to forget them along with the user
+    names: Vec<String>,
+}
+
+/// Everyone who chatted (or was moderated), by their user id
+///
+/// Users are found by their login or display name, in any case.
+pub(crate) struct Users {
+    users: HashMap<String, User>,
+    // logins and display names, lowercased, to the key of their user
+    names: HashMap<String, String>,
+    // the keys by when they were last seen, so the oldest is found without a scan
+    order: BTreeSet<(DateTime<Local>, String)>,
+    users_max: usize,
+    history_max: usize,
+}
+
+impl Default for Users {
+    fn default() -> Self {
+        Self::new(USERS_MAX, HISTORY_MAX)
+    }
+}
+
+impl Users {
+    pub(crate) fn new(users_max: usize, history_max: usize) -> Self {
+        Self {
+            users: HashMap::new(),
+            names: HashMap::new(),
+            order: BTreeSet::new(),
+            users_max,
+            history_max,
+        }
+    }
+
+    pub(crate) fn saw(&mut self, msg: &Message<'static>) {
+        let login = msg.pm.name().to_lowercase();
+        let key = key(&msg.pm);
+
+        // someone who was only moderated so far is only known by their login
+        if key != login && self.names.get(&login) == Some(&login) {
+            if let Some(user) = self.users.remove(&login) {
+                self.order.remove(&(user.last_seen, login.clone()));
+                self.order.insert((user.last_seen, key.clone()));
+                self.users.insert(key.clone(), user);
+            }
+        }
+        let mut names = vec![login.clone()];
+        names.extend(msg.pm.display_name().map(str::to_lowercase));
+        for name in &names {
+            self.names.insert(name.clone(), key.clone());
+        }
+
+        let user = self.seen(key, &login, msg.ts);
+        user.login = login;
+        user.count += 1;
+        user.history.push(msg.clone());
+        for name in names {
+            if !user.names.contains(&name) {
+                user.names.push(name);
+            }
+        }
+        self.forget_oldest();
+    }
+
+    pub(crate) fn moderated(&mut self, login: &str, ts: DateTime<Local>, what: String) {
+        let login = login.to_lowercase();
+        let key = self
+            .names
+            .entry(login.clone())
+            .or_insert_with(|| login.clone());
+        let key = key.clone();
+
+        let user = self.seen(key, &login, ts);
+        if !user.names.contains(&login) {
+            user.names.push(login);
+        }
+        user.moderation.push((ts, what));
+        self.forget_oldest();
+    }
+
+    pub(crate) fn get(&self, name: &str) -> Option<&User> {
+        let key = self.names.get(&name.to_lowercase())?;
+        self.users.get(key)
+    }
+
+    /// Hides (or unhides) these messages in everyone's history, returning the ones that changed
+    pub(crate) fn set_hidden(&mut self, ids: &[usize], hidden: bool) -> Vec<&Message<'static>> {
+        let mut changed = vec![];
+        for user in self.users.values_mut() {
+            for msg in user.history.iter_mut() {
+                if ids.contains(&msg.id) && msg.hidden != hidden {
+                    msg.hidden = hidden;
+                    changed.push(&*msg);
+                }
+            }
+        }
+        changed.sort_by_key(|msg| msg.id);
+        changed
+    }
+
+    // the user, last seen at `ts`
+    fn seen(&mut self, key: String, login: &str, ts: DateTime<Local>) -> &mut User {
+        let history_max = self.history_max;
+        let user = self.users.entry(key.clone()).or_insert_with(|| User {
+            login: login.to_string(),
+            first_seen: ts,
+            last_seen: ts,
+            count: 0,
+            moderation: vec![],
+            history: Queue::with_size(history_max),
+            names: vec![],
+        });
+        self.order.remove(&(user.last_seen, key.clone()));
+        self.order.insert((ts, key));
+        user.last_seen = ts;
+        user
+    }
+
+    fn forget_oldest(&mut self) {
+        while self.users.len() > self.users_max {
+            let (_, oldest) = match self.order.pop_first() {
+                Some(oldest) => oldest,
+                None => return,
+            };
+            let user = match self.users.remove(&oldest) {
+                Some(user) => user,
+                None => continue,
+            };
+            // a name may have moved on to someone else since
+            for name in user.names {
+                if self.names.get(&name) == Some(&oldest) {
+                    self.names.remove(&name);
+                }
+            }
+        }
+    }
+}
+
+// the id stays the same when they change their name
+fn key(pm: &Privmsg<'_>) -> String {
+    match pm.tags().get("user-id").filter(|id| !id.is_empty()) {
+        Some(id) => id.to_string(),
+        None => pm.name().to_lowercase(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    fn message(id: usize, line: &str, secs: i64) -> Message<'static> {
+        let pm = crate::transcript::parse_privmsg(line).unwrap();
+        Message::new(id, pm, Local.timestamp_opt(secs, 0).unwrap())
+    }
+
+    #[test]
+    fn saw() {
+        let ts = |secs| Local.timestamp_opt(secs, 0).unwrap();
+
+        let mut users = Users::new(10, 2);
+        users.moderated("MUSEUN", ts(5), "timed out for 1m".into());
+        for i in 0..3 {
+            let line = format!(
+                "@user-id=1;display-name=Museun :museun!museun@museun PRIVMSG #museun :{}",
+                i
+            );
+            users.saw(&message(i, &line, 10 + i as i64));
+        }
+
+        let user = users.get("museun").unwrap();
+        assert_eq!(user.login, "museun");
+        assert_eq!((user.first_seen, user.last_seen), (ts(5), ts(12)));
+        assert_eq!(user.count, 3);
+        assert_eq!(
+            user.moderation,
+            vec![(ts(5), "timed out for 1m".to_string())]
+        );
+        let history = user
+            .history
+            .iter()
+            .map(|msg| msg.pm.data())
+            .collect::<Vec<_>>();
+        assert_eq!(history, ["1", "2"]);
+
+        // they're found by their display name too, and the id survives a rename
+        let line = "@user-id=1;display-name=Someone :someone!someone@someone PRIVMSG #museun :3";
+        users.saw(&message(3, line, 13));
+        assert_eq!(users.get("SOMEONE").unwrap().count, 4);
+        assert_eq!(users.get("museun").unwrap().count, 4);
+        assert!(users.get("nobody").is_none());
+
+        let hidden = users.set_hidden(&[2, 3], true);
+        assert_eq!(hidden.iter().map(|msg| msg.id).collect::<Vec<_>>(), [2, 3]);
+        assert!(users.set_hidden(&[2], true).is_empty());
+    }
+
+    #[test]
+    fn forget_oldest() {
+        let mut users = Users::new(2, 1);
+        for (i, name) in ["a", "b", "a", "c"].iter().enumerate() {
+            let line = format!(":{name}!{name}@{name} PRIVMSG #museun :hi", name = name);
+            users.saw(&message(i, &line, i as i64));
+        }
+        assert!(users.get("b").is_none());
+        assert_eq!(users.get("a").unwrap().count, 2);
+        assert_eq!(users.get("c").unwrap().count, 1);
+
+        // their names are forgotten with them
+        assert!(!users.names.contains_key("b"));
+        users.moderated("d", Local.timestamp_opt(4, 0).unwrap(), "banned".into());
+        assert!(users.get("a").is_none() && !users.names.contains_key("a"));
+        assert_eq!(users.order.len(), 2);
+    }
+}
@@ -1,146 +1,1168 @@
-use crate::App;
+use crate::{
+    app::Marking,
+    clipboard,
+    filter::Filter,
+    frame::{Canvas, Frame},
+    users::Users,
+    App, Args,
+};
 
-use super::{partition, queue::Queue, truncate};
+use super::{
+    filter,
+    layout::{Layout, Row},
+    link::{self, Linker, Segment},
+    moderate,
+    pane::{self, Pane, PaneSpec, Rect, Split},
+    queue::Queue,
+    reply,
+    timestamp::{self, Timestamp},
+    truncate::{self, Align, Ellipsis},
+    wrap::{self, Whitespace},
+};
 
 use std::{borrow::Cow, io::Write};
 
-use crossterm::{
-    cursor::*,
-    style::*,
-    terminal::{self, *},
-};
+use crossterm::{cursor::*, style::*};
 use twitchchat::{messages::Privmsg, twitch::color::RGB};
 use unicode_width::UnicodeWidthStr;
 
-// TODO make this configurable
-const MAX_COLUMN_WIDTH: usize = 25;
-// TODO make this configurable
-const MIN_COLUMN_WIDTH: usize = 5;
-// TODO make this configurable
-const MIN_WINDOW_WIDTH: usize = 30;
 // TODO make this configurable
 const TS_COLOR: Color = Color::DarkYellow;
+// TODO make this configurable
+const GUTTER_COLOR: Color = Color::DarkGrey;
+// the width of the gutter glyph and the space after it
+const GUTTER_WIDTH: usize = 2;
+// the name column gives way on narrow screens, so the text keeps at least this much
+const MIN_TEXT_WIDTH: usize = 10;
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
 pub enum UpdateMode {
     Redraw,
     Append,
     MarkAll,
+    /// only repaint the status line
+    Status,
+}
+
+/// Where the wrapped lines of a message continue
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Continuation {
+    /// Under the start of the text
+    Align,
+    /// Under the start of the text, behind a `│` (or `↳` for the last line) gutter
+    Hang,
+    /// From the left edge, using the full width
+    Full,
+}
+
+impl Continuation {
+    /// The next style, for cycling through them
+    pub(crate) const fn next(self) -> Self {
+        match self {
+            Self::Align => Self::Hang,
+            Self::Hang => Self::Full,
+            Self::Full => Self::Align,
+        }
+    }
+
+    fn gutter(self, last: bool) -> Option<StyledContent<&'static str>> {
+        let glyph = match (self, last) {
+            (Self::Hang, false) => "│ ",
+            (Self::Hang, true) => "↳ ",
+            _ => return None,
+        };
+        Some(style(glyph).with(GUTTER_COLOR))
+    }
+}
+
+impl std::str::FromStr for Continuation {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "align" => Ok(Self::Align),
+            "hang" => Ok(Self::Hang),
+            "full" => Ok(Self::Full),
+            s => anyhow::bail!("unknown style '{}', expected align, hang or full", s),
+        }
+    }
 }
 
 pub(crate) struct Window {
     queue: Queue<Message<'static>>,
-    left: usize,
-    pad: String,
-    min: Option<usize>,
+    // the chat is the first pane, any others are split off from it
+    panes: Vec<Pane>,
+    split: Split,
+    // where the chat was on the last update
+    main: Rect,
+    // what was drawn on the last update
+    frame: Frame,
+    next_id: usize,
+    // how many (visible) messages we're scrolled back from the newest one
+    scroll: usize,
+    filter: Option<Filter>,
+    search: Option<Search>,
+    // the marks from the last 'MarkAll' and which message they point to
+    marks: Vec<(String, usize)>,
+    // what has been typed of a mark so far
+    typed: String,
+    // the messages picked for deletion, when picking several at once
+    picked: Vec<usize>,
+    // the messages hidden by each deletion, so they can be undone
+    deleted: Vec<Vec<usize>>,
+    // users whose messages are hidden as they come in, with the deletion that muted them
+    muted: Vec<(String, usize)>,
+    // how many rows were available for messages on the last update
+    rows: usize,
+    // which message is on which row, for mapping clicks back to them
+    layout: Layout,
+    // the id of the message that was clicked on
+    selected: Option<usize>,
+    // everyone's recent messages, these are kept after they leave the queue
+    users: Users,
+    // how long a run of the same message gets before it's collapsed
+    collapse: Option<usize>,
+    // the last message was collapsed into an earlier one, rather than appended
+    collapsed: bool,
+    // there was a status line on the last update
+    status_shown: bool,
+}
+
+struct Search {
+    query: String,
+    hit: Option<usize>,
+}
+
+#[derive(Copy, Clone)]
+enum Direction {
+    Here,
+    Older,
+    Newer,
 }
 
 impl Window {
     pub(crate) fn new(left: usize, limit: usize, min: Option<usize>) -> Self {
         Self {
-            left,
-            pad: " ".repeat(left),
             queue: Queue::with_size(limit),
-            min,
+            panes: vec![Pane::new(left, min)],
+            split: Split::Pane(0),
+            main: Rect::default(),
+            frame: Frame::default(),
+            next_id: 0,
+            scroll: 0,
+            filter: None,
+            search: None,
+            marks: vec![],
+            typed: String::new(),
+            picked: vec![],
+            deleted: vec![],
+            muted: vec![],
+            rows: 0,
+            layout: Layout::default(),
+            selected: None,
+            users: Users::default(),
+            collapse: None,
+            collapsed: false,
+            status_shown: false,
+        }
+    }
+
+    /// Collapses runs of the same message once they're `n` long, e.g. spam
+    pub(crate) fn with_collapse(mut self, n: Option<usize>) -> Self {
+        self.collapse = n;
+        self
+    }
+
+    /// Splits panes off the chat, each showing the messages its filter lets through
+    pub(crate) fn with_panes(mut self, specs: &[PaneSpec]) -> Self {
+        for spec in specs {
+            let (left, min) = (self.panes[0].left, self.panes[0].min);
+            let mut pane = Pane::new(spec.nick.unwrap_or(left), min);
+            pane.filter = spec.filter.clone();
+            pane.compact = spec.compact;
+            self.panes.push(pane);
+
+            let split = std::mem::replace(&mut self.split, Split::Pane(0));
+            self.split = split.add(spec.direction, spec.percent, self.panes.len() - 1);
+        }
+        self
+    }
+
+    /// Pushes a message that was received at `received`, it's shown at the time it was sent
+    pub(crate) fn push_received(
+        &mut self,
+        message: Privmsg<'static>,
+        received: chrono::DateTime<chrono::Local>,
+    ) {
+        let sent = timestamp::sent_at(&message);
+        let mut msg = Message::new(self.next_id, message, sent.unwrap_or(received));
+        msg.latency = sent.map(|sent| received - sent);
+        self.push_message(msg)
+    }
+
+    /// Pushes a message from `ts`, e.g. when replaying a transcript
+    pub(crate) fn push_at(
+        &mut self,
+        message: Privmsg<'static>,
+        ts: chrono::DateTime<chrono::Local>,
+    ) {
+        self.push_message(Message::new(self.next_id, message, ts))
+    }
+
+    fn push_message(&mut self, mut msg: Message<'static>) {
+        self.next_id += 1;
+
+        // undoing the mute brings these back too
+        let name = msg.pm.name();
+        if let Some(&(_, deletion)) = self.muted.iter().find(|(muted, _)| muted == name) {
+            msg.hidden = true;
+            self.deleted[deletion].push(msg.id);
+        }
+        self.users.saw(&msg);
+
+        self.collapsed = !msg.hidden && self.collapse(&msg);
+        if !self.collapsed {
+            // keep the scrolled view where it is
+            if self.scroll > 0 && self.is_visible(&msg) {
+                self.scroll += 1;
+            }
+            self.queue.push(msg);
+        }
+        self.scroll = self.scroll.min(self.visible().count().saturating_sub(1));
+    }
+
+    // merges the message into the run of the same message at the end of the
+    // queue, once the run is long enough. this returns whether it was merged
+    fn collapse(&mut self, msg: &Message<'static>) -> bool {
+        let threshold = match self.collapse {
+            Some(threshold) => threshold,
+            None => return false,
+        };
+
+        let text = normalize(msg.pm.data());
+        let run = self
+            .queue
+            .iter()
+            .rev()
+            .take_while(|old| !old.hidden && normalize(old.pm.data()) == text)
+            .count();
+        let first = self.queue.len().saturating_sub(run);
+        let started = self
+            .queue
+            .iter()
+            .nth(first)
+            .is_some_and(|old| old.repeats > 1);
+        if run == 0 || (!started && run + 1 < threshold) {
+            return false;
+        }
+
+        // the scrolled view loses these, so it stays where it is
+        let removed = (self.queue.iter().skip(first + 1))
+            .filter(|old| self.is_visible(old))
+            .count();
+        self.scroll = self.scroll.saturating_sub(removed);
+
+        let rest = self.queue.iter().skip(first + 1);
+        let senders = (rest.map(|old| (old.id, old.pm.name().to_string())))
+            .chain(std::iter::once((msg.id, msg.pm.name().to_string())))
+            .collect::<Vec<_>>();
+        for index in (first + 1..self.queue.len()).rev() {
+            self.queue.remove(index);
+        }
+        if self
+            .selected
+            .is_some_and(|id| senders.iter().any(|(old, _)| *old == id))
+        {
+            self.selected = None;
+        }
+
+        if let Some(first) = self.queue.iter_mut().nth(first) {
+            for (_, sender) in senders {
+                first.repeated(sender);
+            }
         }
+        true
     }
 
-    pub(crate) fn push(&mut self, message: Privmsg<'static>) {
-        self.queue.push(Message::new(message));
+    /// Forgets what was drawn, so the next redraw draws every row
+    pub(crate) fn invalidate(&mut self) {
+        self.frame.invalidate();
     }
 
     pub(crate) fn update(&mut self, app: &mut App, update: UpdateMode) -> anyhow::Result<()> {
-        let (width, height) = terminal::size()?;
-        let mut stdout = std::io::stdout();
+        let (width, height) = app.terminal.size()?;
+
+        let status = self.status(app);
+        let status = status.map(|s| truncate::truncate_or_pad(&s, width as _));
+        let rows = (height as usize).saturating_sub(status.is_some() as usize);
+
+        let screen = Rect {
+            x: 0,
+            y: 0,
+            width,
+            height: rows as _,
+        };
+        let (panes, dividers) = self.split.rects(screen);
+        let main = (panes.iter())
+            .find(|(index, _)| *index == 0)
+            .map_or(screen, |&(_, rect)| rect);
+        self.main = main;
+        self.rows = main.height as _;
+        app.view_mode = self.panes[0].view_mode(main.width);
+
+        let overlay = app.overlay.clone();
+        let popup = match overlay {
+            // the chat is drawn again once the overlay is closed
+            Some(overlay) if update == UpdateMode::Append && !overlay.is_popup() => return Ok(()),
+            Some(overlay) if !overlay.is_popup() => {
+                let lines = overlay.lines(app, self);
+                let mut canvas = Canvas::new((width, height));
+                overlay.print(&mut canvas, &lines, (width, rows))?;
+                if let Some(status) = &status {
+                    print_status(&mut canvas, status, height)?;
+                }
+                self.layout.clear(height);
+                let stdout = &mut app.terminal;
+                self.frame.draw(stdout, canvas)?;
+                stdout.flush()?;
+                return Ok(());
+            }
+            // popups are drawn over the chat, once it has been drawn
+            Some(overlay) => Some((overlay.lines(app, self), overlay)),
+            None => None,
+        };
+
+        let stdout = &mut app.terminal;
+        let split = !self.split.is_single();
 
-        app.view_mode = if (width as usize) < self.min.unwrap_or(MIN_WINDOW_WIDTH) {
-            ViewMode::Compact
-        } else {
-            ViewMode::Normal
+        let update = match update {
+            // the popup has to be drawn over the chat again
+            UpdateMode::Append | UpdateMode::Status if popup.is_some() => UpdateMode::Redraw,
+            // the message is already on the screen, it just has to be updated. the
+            // frame only writes the rows that changed, so this isn't a full repaint
+            UpdateMode::Append if std::mem::take(&mut self.collapsed) => UpdateMode::Redraw,
+            // the other panes can't be scrolled along with the chat
+            UpdateMode::Append if split => UpdateMode::Redraw,
+            // and the old status line has to go
+            UpdateMode::Append if status.is_none() && self.status_shown => UpdateMode::Redraw,
+            // the status line has to stay at the bottom, so just redraw
+            UpdateMode::Append if status.is_some() => match self.queue.last() {
+                // e.g. the hidden count went up
+                Some(msg) if !self.is_visible(msg) => UpdateMode::Status,
+                Some(..) if self.scroll > 0 => UpdateMode::Status,
+                _ => UpdateMode::Redraw,
+            },
+            UpdateMode::Status if status.is_none() => UpdateMode::Redraw,
+            update => update,
         };
+        let (args, view_mode) = (&app.args, app.view_mode);
+        let size = (width, height);
+
+        let canvas = match update {
+            UpdateMode::Redraw if self.queue.is_empty() && status.is_none() && !split => {
+                return Ok(())
+            }
+
+            UpdateMode::Redraw | UpdateMode::MarkAll if split => {
+                let marking = update == UpdateMode::MarkAll && view_mode == ViewMode::Normal;
+                let mut canvas = Canvas::new(size);
+                let mut layout = Layout::default();
+                layout.clear(main.height);
 
-        match update {
-            UpdateMode::Redraw if self.queue.is_empty() => return Ok(()),
+                for &(index, rect) in &panes {
+                    if index != 0 {
+                        self.print_pane(&mut canvas, &self.panes[index], rect, args)?;
+                        continue;
+                    }
+                    // the chat is printed as if it had the whole screen, then moved into its pane
+                    let mut rendered = vec![];
+                    let size = (main.width, main.height as _);
+                    let marks = self.print_chat(&mut rendered, &mut layout, size, args, marking)?;
+                    canvas.cursor = canvas.put_rendered(&rendered, rect)?;
+                    if marking {
+                        self.marks = marks;
+                    }
+                }
+                for &(direction, rect) in &dividers {
+                    pane::print_divider(&mut canvas, direction, rect)?;
+                }
+                if let Some(status) = &status {
+                    print_status(&mut canvas, status, height)?;
+                }
+                self.layout = layout;
+                Some(canvas)
+            }
 
             UpdateMode::Redraw => {
-                crossterm::execute!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
-                for msg in self.queue.iter().rev().take(height as _).rev() {
-                    let state = self.state(width, app.args.timestamps);
-                    app.view_mode.print_message(&mut stdout, msg, state)?;
+                let mut layout = Layout::default();
+                layout.clear(height);
+
+                let mut rendered = vec![];
+                self.print_chat(&mut rendered, &mut layout, (width, rows), args, false)?;
+                let canvas = print_screen(rendered, &mut layout, status.as_deref(), size)?;
+                self.layout = layout;
+                Some(canvas)
+            }
+
+            UpdateMode::Status => {
+                if let Some(status) = &status {
+                    let mut row = vec![];
+                    crossterm::queue!(row, Print(style(status).reverse()))?;
+                    let bottom = height.saturating_sub(1);
+                    self.frame.draw_row(stdout, size, bottom, row)?;
                 }
+                None
             }
 
             UpdateMode::Append => {
                 if let Some(msg) = self.queue.last() {
                     if self.queue.len() == 1 {
                         crossterm::execute!(stdout, MoveTo(0, 0))?;
+                        self.layout.clear(height);
+                        self.frame.home();
                     }
-                    let state = self.state(width, app.args.timestamps);
-                    app.view_mode.print_message(&mut stdout, msg, state)?;
+                    // the terminal scrolls the chat up, rather than it being drawn again
+                    let mut rendered = vec![];
+                    let state = self.state(width, args, view_mode);
+                    for row in view_mode.print_message(&mut rendered, msg, state)? {
+                        self.layout.line_feed(row);
+                    }
+                    stdout.write_all(&rendered)?;
+                    self.frame.append(size, &rendered);
                 }
+                None
             }
 
-            UpdateMode::MarkAll if matches!(app.view_mode, ViewMode::Normal) => {
-                crossterm::queue!(stdout, Clear(ClearType::All), MoveTo(0, 0))?;
+            UpdateMode::MarkAll if matches!(view_mode, ViewMode::Normal) => {
+                let mut layout = Layout::default();
+                layout.clear(height);
 
-                let iter = self.queue.iter().rev().take((height) as _).rev();
-                let mut ch = ALPHA.iter().take(iter.len()).rev();
+                let mut rendered = vec![];
+                let marks =
+                    self.print_chat(&mut rendered, &mut layout, (width, rows), args, true)?;
+                let canvas = print_screen(rendered, &mut layout, status.as_deref(), size)?;
+                self.marks = marks;
+                self.layout = layout;
+                Some(canvas)
+            }
+            _ => None,
+        };
 
-                for msg in iter {
-                    let mut state = self.state(width, app.args.timestamps);
-                    // this'll stop printing deletion marks if we've reached the
-                    // end of our alphabet
-                    state.prefix = ch.next().copied();
-                    app.view_mode.print_message(&mut stdout, msg, state)?;
-                }
+        if let Some(mut canvas) = canvas {
+            if let Some((lines, overlay)) = &popup {
+                overlay.print(&mut canvas, lines, (width, rows))?;
             }
-            _ => {}
+            self.frame.draw(stdout, canvas)?;
         }
 
+        self.status_shown = status.is_some();
         stdout.flush()?;
         Ok(())
     }
 
-    pub(crate) fn delete(&mut self, ch: char, app: &mut App) -> anyhow::Result<()> {
-        if let Some(p) = ALPHA.iter().position(|&c| c == ch) {
-            let index = self.queue.len() - p - 1;
-            self.queue.remove(index)
+    // prints the messages that fit in `rows`, with a mark on each of them when
+    // `marking`. this returns the marks
+    fn print_chat(
+        &self,
+        stdout: &mut impl Write,
+        layout: &mut Layout,
+        (width, rows): (u16, usize),
+        args: &Args,
+        marking: bool,
+    ) -> anyhow::Result<Vec<(String, usize)>> {
+        let view_mode = self.panes[0].view_mode(width);
+        let shown = self.shown(rows);
+
+        let mut marks = vec![];
+        if marking {
+            // only the messages that fit entirely on the screen get a
+            // mark, a wrapped message takes up several rows
+            let mut lines = 0;
+            let on_screen = shown
+                .iter()
+                .rev()
+                .take_while(|msg| {
+                    let state = self.state(width, args, view_mode);
+                    let printed = view_mode.print_message(&mut std::io::sink(), msg, state);
+                    lines += printed.map(|rows| rows.len()).unwrap_or(0);
+                    lines <= rows
+                })
+                .count();
+
+            // the newest message gets the first mark
+            marks = shown
+                .iter()
+                .rev()
+                .zip(labels(on_screen))
+                .map(|(msg, label)| (label, msg.id))
+                .collect::<Vec<_>>();
+        }
+
+        for msg in shown {
+            let mut state = self.state(width, args, view_mode);
+            if marking {
+                state.prefix = marks
+                    .iter()
+                    .find(|(_, id)| *id == msg.id)
+                    .map(|(label, _)| &**label);
+                state.typed = &self.typed;
+                state.picked = self.picked.contains(&msg.id);
+            } else {
+                state.highlight = self.is_hit(msg);
+                state.selected = self.selected == Some(msg.id);
+            }
+            for row in view_mode.print_message(stdout, msg, state)? {
+                layout.line_feed(row);
+            }
         }
-        self.update(app, UpdateMode::Redraw)
+        Ok(marks)
     }
 
-    pub(crate) fn grow_nick_column(&mut self) -> bool {
-        if self.left == MAX_COLUMN_WIDTH {
-            return false;
+    // a pane other than the chat: its filter on the top row, and the newest
+    // of the messages it lets through under that
+    fn print_pane(
+        &self,
+        canvas: &mut Canvas,
+        pane: &Pane,
+        rect: Rect,
+        args: &Args,
+    ) -> anyhow::Result<()> {
+        let title = (pane.filter.as_ref()).map_or_else(|| "all".to_string(), ToString::to_string);
+        let title = truncate::truncate_or_pad(&title, rect.width as _);
+        let title = style(title.trim_end()).attribute(Attribute::Bold);
+        crossterm::queue!(canvas.at(rect.x, rect.y)?, Print(title))?;
+
+        let body = Rect {
+            y: rect.y + 1,
+            height: rect.height.saturating_sub(1),
+            ..rect
+        };
+        let view_mode = pane.view_mode(body.width);
+        let mut shown = (self.queue.iter().rev())
+            .filter(|msg| !msg.hidden)
+            .filter(|msg| pane.filter.as_ref().map_or(true, |f| f.matches(&msg.pm)))
+            .take(body.height as _)
+            .collect::<Vec<_>>();
+        shown.reverse();
+
+        let mut rendered = vec![];
+        for msg in shown {
+            let state = pane_state(pane, body.width, args, view_mode);
+            view_mode.print_message(&mut rendered, msg, state)?;
         }
+        canvas.put_rendered(&rendered, body)?;
+        Ok(())
+    }
 
-        self.left += 1;
-        // TODO this could just truncate or append spaces instead of using an entirely new allocation
-        self.pad = " ".repeat(self.left);
-        true
+    /// Types the next character of a mark
+    ///
+    /// This returns the id of the marked message once a whole mark has been
+    /// typed. Typing something that isn't the start of a mark starts over.
+    pub(crate) fn type_mark(&mut self, ch: char) -> Option<usize> {
+        self.typed.push(ch);
+        let typed = &*self.typed;
+
+        if let Some(&(_, id)) = self.marks.iter().find(|(label, _)| label == typed) {
+            self.typed.clear();
+            return Some(id);
+        }
+        if !self.marks.iter().any(|(label, _)| label.starts_with(typed)) {
+            self.typed.clear();
+        }
+        None
     }
 
-    pub(crate) fn shrink_nick_column(&mut self) -> bool {
-        if self.left == MIN_COLUMN_WIDTH {
-            return false;
+    /// Deletes this message
+    ///
+    /// Deleted messages are only hidden, so they can be brought back with
+    /// [`Window::undo`]. This returns them as `nick: text` lines.
+    pub(crate) fn delete(&mut self, id: usize) -> Vec<String> {
+        self.clear_marks();
+        self.hide(vec![id])
+    }
+
+    /// Deletes every message from the user of this message
+    pub(crate) fn delete_user(&mut self, id: usize) -> Vec<String> {
+        let user = self.message(id).map(|msg| msg.pm.name().to_string());
+        self.clear_marks();
+
+        let ids = self
+            .visible()
+            .filter(|msg| Some(msg.pm.name()) == user.as_deref())
+            .map(|msg| msg.id)
+            .collect();
+        self.hide(ids)
+    }
+
+    /// Deletes every message from the user of this message, and the ones they send after
+    ///
+    /// Undoing this unmutes them.
+    pub(crate) fn mute(&mut self, id: usize) -> Vec<String> {
+        let user = match self.message(id) {
+            Some(msg) => msg.pm.name().to_string(),
+            None => return vec![],
+        };
+
+        let hidden = self.delete_user(id);
+        if !hidden.is_empty() {
+            self.muted.push((user, self.deleted.len() - 1));
         }
+        hidden
+    }
 
-        self.left -= 1;
-        // TODO this could just truncate or append spaces instead of using an entirely new allocation
-        self.pad = " ".repeat(self.left);
-        true
+    /// Picks (or unpicks) this message, for deleting several at once
+    pub(crate) fn pick(&mut self, id: usize) {
+        match self.picked.iter().position(|&picked| picked == id) {
+            Some(index) => {
+                self.picked.remove(index);
+            }
+            None => self.picked.push(id),
+        }
+    }
+
+    /// Deletes all of the picked messages
+    pub(crate) fn delete_picked(&mut self) -> Vec<String> {
+        let ids = std::mem::take(&mut self.picked);
+        self.clear_marks();
+        self.hide(ids)
+    }
+
+    /// Brings back the messages from the last deletion, returning them as `nick: text` lines
+    pub(crate) fn undo(&mut self) -> Vec<String> {
+        let ids = match self.deleted.pop() {
+            Some(ids) => ids,
+            None => return vec![],
+        };
+
+        let deletions = self.deleted.len();
+        self.muted.retain(|&(_, deletion)| deletion < deletions);
+        self.set_hidden(&ids, false)
+    }
+
+    pub(crate) fn clear_marks(&mut self) {
+        self.marks.clear();
+        self.typed.clear();
+        self.picked.clear();
+    }
+
+    fn hide(&mut self, ids: Vec<usize>) -> Vec<String> {
+        let hidden = self.set_hidden(&ids, true);
+        if hidden.is_empty() {
+            return hidden;
+        }
+
+        if self.selected.is_some_and(|id| ids.contains(&id)) {
+            self.selected = None;
+        }
+        self.scroll = self.scroll.min(self.visible().count().saturating_sub(1));
+        self.deleted.push(ids);
+        hidden
+    }
+
+    // older messages are only in their user's history, so both copies are changed
+    fn set_hidden(&mut self, ids: &[usize], hidden: bool) -> Vec<String> {
+        let mut lines = vec![];
+        let mut found = vec![];
+        for msg in self.queue.iter_mut().filter(|msg| ids.contains(&msg.id)) {
+            msg.hidden = hidden;
+            lines.push(clipboard::Format::Line.render(&msg.pm));
+            found.push(msg.id);
+        }
+
+        let older = self
+            .users
+            .set_hidden(ids, hidden)
+            .into_iter()
+            .filter(|msg| !found.contains(&msg.id))
+            .map(|msg| clipboard::Format::Line.render(&msg.pm))
+            .collect::<Vec<_>>();
+        older.into_iter().chain(lines).collect()
     }
 
-    fn state(&self, width: u16, show_timestamp: bool) -> State<'_> {
-        State {
-            prefix: None,
-            left: self.left,
-            width: width as _,
-            pad: &self.pad,
-            indent: "",
-            show_timestamp,
+    /// Renders this message for copying
+    pub(crate) fn copy(&mut self, id: usize, format: clipboard::Format) -> Option<String> {
+        self.clear_marks();
+        self.copy_ids(Some(id), format)
+    }
+
+    /// Shows only the reply thread this message is a part of
+    pub(crate) fn expand_thread(&mut self, id: usize) -> bool {
+        self.clear_marks();
+        match self.message(id).and_then(|msg| reply::thread(&msg.pm)) {
+            Some(thread) => {
+                self.set_filter(Some(Filter::Thread(thread)));
+                true
+            }
+            None => false,
         }
     }
+
+    pub(crate) const fn users(&self) -> &Users {
+        &self.users
+    }
+
+    pub(crate) fn users_mut(&mut self) -> &mut Users {
+        &mut self.users
+    }
+
+    pub(crate) const fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    /// Who to act on for the marked message
+    pub(crate) fn target(&mut self, id: usize) -> Option<moderate::Target> {
+        self.clear_marks();
+        let msg = self.message(id)?;
+        Some(moderate::Target::new(&msg.pm))
+    }
+
+    /// Renders the selected message for copying
+    pub(crate) fn copy_selected(&self, format: clipboard::Format) -> Option<String> {
+        self.copy_ids(self.selected, format)
+    }
+
+    /// Renders every marked message for copying, a line each
+    pub(crate) fn copy_marked(&mut self, format: clipboard::Format) -> Option<String> {
+        let ids = self
+            .marks
+            .iter()
+            .rev()
+            .map(|&(_, id)| id)
+            .collect::<Vec<_>>();
+        self.clear_marks();
+        self.copy_ids(ids, format)
+    }
+
+    /// Renders the newest `n` visible messages for copying, a line each
+    pub(crate) fn copy_last(&self, n: usize, format: clipboard::Format) -> Option<String> {
+        let mut ids = self
+            .visible()
+            .rev()
+            .take(n)
+            .map(|msg| msg.id)
+            .collect::<Vec<_>>();
+        ids.reverse();
+        self.copy_ids(ids, format)
+    }
+
+    fn copy_ids(
+        &self,
+        ids: impl IntoIterator<Item = usize>,
+        format: clipboard::Format,
+    ) -> Option<String> {
+        let lines = ids
+            .into_iter()
+            .filter_map(|id| self.message(id))
+            .map(|msg| format.render(&msg.pm))
+            .collect::<Vec<_>>();
+        Some(lines.join("\n")).filter(|_| !lines.is_empty())
+    }
+
+    /// Deletes the selected message, if it's still around
+    pub(crate) fn delete_selected(&mut self) -> Vec<String> {
+        let id = self.selected.take();
+        self.hide(id.into_iter().collect())
+    }
+
+    /// Handles a click on the screen
+    ///
+    /// Clicking a name filters the view to that user, clicking anywhere else
+    /// on a message (un)selects it.
+    pub(crate) fn click(&mut self, column: u16, row: u16) -> bool {
+        // the other panes are only for reading
+        let layout = Some(&self.layout).filter(|_| self.main.contains(column, row));
+        let (id, on_name) = match layout.and_then(|layout| layout.get(row)) {
+            Some(Row { id, name }) => (*id, name.as_ref().is_some_and(|r| r.contains(&column))),
+            None => return self.selected.take().is_some(),
+        };
+
+        if !on_name {
+            self.selected = match self.selected {
+                Some(selected) if selected == id => None,
+                _ => Some(id),
+            };
+            return true;
+        }
+
+        match self.message(id) {
+            Some(msg) => {
+                let name = msg.pm.name().to_lowercase();
+                self.set_filter(Some(Filter::Name(name)));
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn scroll_up(&mut self, lines: usize) -> bool {
+        let max = self.visible().count().saturating_sub(1);
+        let old = self.scroll;
+        self.scroll = self.scroll.saturating_add(lines).min(max);
+        old != self.scroll
+    }
+
+    pub(crate) fn scroll_down(&mut self, lines: usize) -> bool {
+        let old = self.scroll;
+        self.scroll = self.scroll.saturating_sub(lines);
+        old != self.scroll
+    }
+
+    pub(crate) fn page_size(&self) -> usize {
+        (self.rows / 2).max(1)
+    }
+
+    pub(crate) fn set_filter(&mut self, filter: Option<Filter>) {
+        self.filter = filter;
+        self.scroll = 0;
+        if let Some(search) = &mut self.search {
+            search.hit = None;
+        }
+    }
+
+    /// Starts a new search from the bottom of the current view
+    pub(crate) fn search(&mut self, query: &str) {
+        let query = query.trim();
+        if query.is_empty() {
+            self.search = None;
+            return;
+        }
+
+        self.search = Some(Search {
+            query: query.to_string(),
+            hit: None,
+        });
+        self.find(self.scroll, Direction::Here);
+    }
+
+    pub(crate) fn search_older(&mut self) -> bool {
+        self.find_from_hit(Direction::Older)
+    }
+
+    pub(crate) fn search_newer(&mut self) -> bool {
+        self.find_from_hit(Direction::Newer)
+    }
+
+    /// Clears any search, filter and selection, returning to the newest message
+    pub(crate) fn reset(&mut self) -> bool {
+        let changed = self.search.is_some()
+            || self.filter.is_some()
+            || self.selected.is_some()
+            || self.scroll > 0;
+        self.search = None;
+        self.filter = None;
+        self.selected = None;
+        self.scroll = 0;
+        changed
+    }
+
+    fn find_from_hit(&mut self, direction: Direction) -> bool {
+        let hit = match &self.search {
+            Some(Search { hit: Some(hit), .. }) => *hit,
+            Some(..) => return self.find(self.scroll, Direction::Here),
+            None => return false,
+        };
+
+        let cur = self.visible().rev().position(|msg| msg.id == hit);
+        match cur {
+            Some(cur) => self.find(cur, direction),
+            None => self.find(self.scroll, Direction::Here),
+        }
+    }
+
+    // positions are counted backwards from the newest visible message
+    fn find(&mut self, cur: usize, direction: Direction) -> bool {
+        let query = match &self.search {
+            Some(search) => &search.query,
+            None => return false,
+        };
+
+        let found = {
+            let mut candidates = self
+                .visible()
+                .rev()
+                .enumerate()
+                .filter(|(_, msg)| filter::contains_ignore_case(msg.pm.data(), query));
+
+            match direction {
+                Direction::Here => candidates.find(|&(pos, _)| pos >= cur),
+                Direction::Older => candidates.find(|&(pos, _)| pos > cur),
+                Direction::Newer => candidates.take_while(|&(pos, _)| pos < cur).last(),
+            }
+            .map(|(pos, msg)| (pos, msg.id))
+        };
+
+        match found {
+            Some((pos, id)) => {
+                self.scroll = pos;
+                self.search.as_mut().expect("search is active").hit = Some(id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn is_visible(&self, msg: &Message<'_>) -> bool {
+        !msg.hidden && self.filter.as_ref().map_or(true, |f| f.matches(&msg.pm))
+    }
+
+    fn is_hit(&self, msg: &Message<'_>) -> bool {
+        matches!(&self.search, Some(Search { hit: Some(id), .. }) if *id == msg.id)
+    }
+
+    fn visible(&self) -> impl DoubleEndedIterator<Item = &Message<'static>> + '_ {
+        // a user's history goes back further than the queue does
+        let oldest = self.queue.iter().next().map_or(self.next_id, |msg| msg.id);
+        let older = match &self.filter {
+            Some(Filter::Name(name)) => self.users.get(name),
+            _ => None,
+        };
+        let older = older
+            .into_iter()
+            .flat_map(|user| user.history.iter())
+            .filter(move |msg| msg.id < oldest);
+
+        older
+            .chain(self.queue.iter())
+            .filter(move |msg| self.is_visible(msg))
+    }
+
+    // a message that is in the queue, or can still be seen through a filter
+    fn message(&self, id: usize) -> Option<&Message<'static>> {
+        (self.queue.iter().find(|msg| msg.id == id))
+            .or_else(|| self.visible().find(|msg| msg.id == id))
+    }
+
+    // the messages that should be on the screen, oldest first
+    fn shown(&self, rows: usize) -> Vec<&Message<'static>> {
+        let mut shown = self
+            .visible()
+            .rev()
+            .skip(self.scroll)
+            .take(rows)
+            .collect::<Vec<_>>();
+        shown.reverse();
+        shown
+    }
+
+    fn status(&self, app: &App) -> Option<String> {
+        if let Some(prompt) = &app.prompt {
+            return Some(prompt.render());
+        }
+        if let Some(pending) = &app.confirming {
+            return Some(format!("{} y/n", pending.question));
+        }
+        if let Some(overlay) = &app.overlay {
+            return Some(overlay.status());
+        }
+        match app.marking {
+            _ if !app.waiting => {}
+            Marking::Delete => {}
+            Marking::DeleteMany => {
+                return Some(format!(
+                    "delete {}: pick marks, enter to delete",
+                    self.picked.len()
+                ))
+            }
+            Marking::DeleteUser => return Some("delete everything from: press a mark".to_string()),
+            Marking::Copy => {
+                return Some(format!(
+                    "copy {}: press a mark, * for all, tab to change the format",
+                    app.args.copy_format
+                ))
+            }
+            Marking::Moderate if app.action.is_channel_wide() => {
+                return Some(format!("{}: enter to toggle, tab to change", app.action))
+            }
+            Marking::Moderate => {
+                return Some(format!("{}: press a mark, tab to change", app.action))
+            }
+            Marking::Thread => return Some("thread: press a mark".to_string()),
+            Marking::UserInfo => return Some("user info: press a mark".to_string()),
+            Marking::Mute => return Some("mute: press a mark".to_string()),
+        }
+
+        let mut parts = vec![];
+        if let Some(notice) = &app.notice {
+            parts.push(notice.clone());
+        }
+        if let Some(throttle) = &app.throttle {
+            if throttle.behind() > 0 {
+                parts.push(format!("{} behind", throttle.behind()));
+            }
+            if throttle.skipped() > 0 {
+                parts.push(format!("{} skipped", throttle.skipped()));
+            }
+        }
+        match app.unread {
+            0 => {}
+            1 => parts.push("1 whisper".to_string()),
+            n => parts.push(format!("{} whispers", n)),
+        }
+        if let Some(filter) = &self.filter {
+            parts.push(format!("filter: {}", filter));
+        }
+        if let Some(search) = &self.search {
+            let suffix = if search.hit.is_none() {
+                " (no match)"
+            } else {
+                ""
+            };
+            parts.push(format!("search: {}{}", search.query, suffix));
+        }
+        if self.scroll > 0 {
+            parts.push(format!("{} newer", self.scroll));
+        }
+        let hidden = self.queue.iter().filter(|msg| msg.hidden).count();
+        if hidden > 0 {
+            parts.push(format!("{} hidden", hidden));
+        }
+
+        if parts.is_empty() {
+            return None;
+        }
+        Some(parts.join(" | "))
+    }
+
+    pub(crate) fn grow_nick_column(&mut self) -> bool {
+        self.panes[0].grow_nick_column()
+    }
+
+    pub(crate) fn shrink_nick_column(&mut self) -> bool {
+        self.panes[0].shrink_nick_column()
+    }
+
+    fn state<'a>(&'a self, width: u16, args: &'a Args, view_mode: ViewMode) -> State<'a> {
+        pane_state(&self.panes[0], width, args, view_mode)
+    }
+}
+
+fn pane_state<'a>(pane: &'a Pane, width: u16, args: &'a Args, view_mode: ViewMode) -> State<'a> {
+    State {
+        prefix: None,
+        typed: "",
+        picked: false,
+        left: pane.left,
+        width: width as _,
+        pad: &pane.pad,
+        continuation: args.continuation(view_mode),
+        align: args.nick_align,
+        ellipsis: args.nick_ellipsis,
+        separator: args.nick_separator.as_deref(),
+        timestamp: Some(&args.timestamp).filter(|_| args.timestamps),
+        now: chrono::Local::now(),
+        show_latency: args.latency,
+        highlight: false,
+        selected: false,
+        links: args.links,
+        whitespace: args.whitespace,
+    }
+}
+
+// the part of the mark that has been typed is dimmed, as is any mark it isn't the start of
+fn print_prefix(
+    stdout: &mut impl Write,
+    prefix: &str,
+    typed: &str,
+    picked: bool,
+) -> anyhow::Result<()> {
+    let color = match picked {
+        true => Color::Red,
+        false => Color::Yellow,
+    };
+    let (done, rest) = match prefix.strip_prefix(typed) {
+        Some(rest) => (typed, rest),
+        None => (prefix, ""),
+    };
+
+    crossterm::queue!(stdout, Print(style(done).with(Color::DarkGrey)))?;
+    match picked {
+        true => crossterm::queue!(stdout, Print(style(rest).with(color).reverse()))?,
+        false => crossterm::queue!(stdout, Print(style(rest).with(color)))?,
+    }
+    Ok(())
+}
+
+// prints a line of a message's text, with its urls as hyperlinks if there's a linker
+fn print_text(
+    stdout: &mut impl Write,
+    line: &str,
+    linker: Option<&mut Linker<'_>>,
+    selected: bool,
+) -> anyhow::Result<()> {
+    let segments = match linker {
+        Some(linker) => linker.line(line),
+        None => vec![Segment::Text(line)],
+    };
+
+    for segment in segments {
+        let text = match segment {
+            Segment::Text(text) => Cow::Borrowed(text),
+            Segment::Link { text, url } => Cow::Owned(link::hyperlink(text, url)),
+        };
+        match selected {
+            true => crossterm::queue!(stdout, Print(style(text).reverse()))?,
+            false => crossterm::queue!(stdout, Print(text))?,
+        }
+    }
+    Ok(())
+}
+
+// the status line goes on the bottom row, over whatever was there
+fn print_status(canvas: &mut Canvas, status: &str, height: u16) -> anyhow::Result<()> {
+    let row = canvas.at(0, height.saturating_sub(1))?;
+    crossterm::queue!(row, Print(style(status).reverse()))?;
+    Ok(())
+}
+
+// the chat on the whole screen, with the status line under it
+fn print_screen(
+    mut rendered: Vec<u8>,
+    layout: &mut Layout,
+    status: Option<&str>,
+    (width, height): (u16, u16),
+) -> anyhow::Result<Canvas> {
+    let mut canvas = Canvas::new((width, height));
+    if status.is_some() {
+        // the chat is scrolled up a line to make room for it
+        rendered.push(b'\n');
+        layout.line_feed(None);
+        layout.status();
+    }
+
+    let screen = Rect {
+        x: 0,
+        y: 0,
+        width,
+        height,
+    };
+    canvas.cursor = canvas.put_rendered(&rendered, screen)?;
+    if let Some(status) = status {
+        print_status(&mut canvas, status, height)?;
+    }
+    Ok(canvas)
+}
+
+/// Marks for `n` messages
+///
+/// These are single characters while there are enough of them, otherwise
+/// they're all as long as they need to be. That way no mark is the start of
+/// another one, so they can be typed one character at a time.
+fn labels(n: usize) -> Vec<String> {
+    let mut len = 1;
+    while ALPHA.len().pow(len) < n {
+        len += 1;
+    }
+
+    (0..n)
+        .map(|mut i| {
+            let mut label = vec![' '; len as usize];
+            for ch in label.iter_mut().rev() {
+                *ch = ALPHA[i % ALPHA.len()];
+                i /= ALPHA.len();
+            }
+            label.into_iter().collect()
+        })
+        .collect()
 }
 
 #[rustfmt::skip]
@@ -159,12 +1181,28 @@ const ALPHA: &[char] = &[
 ];
 
 struct State<'a> {
-    prefix: Option<char>,
+    prefix: Option<&'a str>,
+    // what has been typed of the prefix so far
+    typed: &'a str,
+    // the prefix is picked for deletion
+    picked: bool,
     left: usize,
     width: usize,
-    show_timestamp: bool,
+    // shown when this is set
+    timestamp: Option<&'a Timestamp>,
+    now: chrono::DateTime<chrono::Local>,
+    show_latency: bool,
+    highlight: bool,
+    // the text is reversed when this is set
+    selected: bool,
+    // print urls as hyperlinks
+    links: bool,
+    whitespace: Whitespace,
     pad: &'a str,
-    indent: &'a str,
+    continuation: Continuation,
+    align: Align,
+    ellipsis: Ellipsis,
+    separator: Option<&'a str>,
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, PartialOrd, Eq, Ord, Hash)]
@@ -174,12 +1212,13 @@ pub enum ViewMode {
 }
 
 impl ViewMode {
+    /// Prints the message, returning what ended up on each line it printed
     fn print_message(
         &self,
         stdout: &mut impl Write,
         msg: &Message<'_>,
         state: State<'_>,
-    ) -> anyhow::Result<()> {
+    ) -> anyhow::Result<Vec<Option<Row>>> {
         let print = match self {
             Self::Normal => Self::print_normal,
             Self::Compact => Self::print_compact,
@@ -194,48 +1233,132 @@ impl ViewMode {
         msg: &Message<'_>,
         state: State<'_>,
         color: Color,
-    ) -> anyhow::Result<()> {
-        let p = state.prefix.map(|_| 4).unwrap_or(0);
+    ) -> anyhow::Result<Vec<Option<Row>>> {
+        // the prefix is taken out of the name column
+        let p = state.prefix.map(|prefix| prefix.width() + 3).unwrap_or(0);
 
-        let name = truncate::truncate_or_pad(msg.pm.name(), state.left - p);
-        let name = style(name).with(color);
+        // the separator is followed by a space
+        let separator = state.separator.map(|sep| style(sep).with(GUTTER_COLOR));
+        let sep = state.separator.map(|sep| sep.width() + 1).unwrap_or(0);
 
-        let partition = partition::partition(
-            msg.pm.data(),
-            state.width - p - state.left - 1 - state.indent.len(),
-        );
+        let ts = state
+            .timestamp
+            .map(|timestamp| timestamp.column(&msg.ts, &state.now));
+        let ts = match (ts, state.show_latency) {
+            (Some(ts), true) => Some(format!("{} {}", ts, timestamp::latency(msg.latency))),
+            (None, true) => Some(timestamp::latency(msg.latency)),
+            (ts, false) => ts,
+        };
+        // the timestamp is followed by a space
+        let ts_width = ts.as_ref().map(|ts| ts.width() + 1).unwrap_or(0);
+
+        // everything has to fit on the screen, otherwise it's shown compactly
+        let left =
+            (state.left).min((state.width).saturating_sub(ts_width + 1 + sep + MIN_TEXT_WIDTH));
+        if left < pane::MIN_COLUMN_WIDTH.max(p + 1) {
+            return Self::print_compact(stdout, msg, state, color);
+        }
+        let text = state.width - (ts_width + left + 1 + sep);
 
-        for (i, part) in partition.into_iter().enumerate() {
+        let name = truncate::fit(msg.pm.name(), left - p, state.align, state.ellipsis);
+        let name = match state.highlight {
+            true => style(name).with(color).reverse(),
+            false => style(name).with(color),
+        };
+        let data = msg.pm.data();
+        let lines = match state.continuation {
+            Continuation::Align => wrap::wrap(data, text, state.whitespace),
+            Continuation::Hang => {
+                let rest = text.saturating_sub(GUTTER_WIDTH);
+                wrap::wrap_hanging(data, text, rest, state.whitespace)
+            }
+            Continuation::Full => wrap::wrap_hanging(data, text, state.width, state.whitespace),
+        };
+        let last = lines.len().saturating_sub(1);
+
+        let mut linker = Some(Linker::new(data)).filter(|_| state.links);
+        let mut rows = Vec::with_capacity(lines.len() + 1);
+
+        // replies say what they're replying to, above the text
+        if let Some(parent) = reply::Parent::new(&msg.pm) {
+            let indent = ts_width + left + 1 + sep;
+            crossterm::queue!(
+                stdout,
+                Print("\n"),
+                MoveToColumn(0),
+                Print(" ".repeat(indent)),
+                Print(style(parent.context(text)).with(GUTTER_COLOR))
+            )?;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: None,
+            }));
+        }
+        // as do collapsed repeats, with who sent them
+        if let Some(repeats) = msg.repeats_line(text) {
+            let indent = ts_width + left + 1 + sep;
+            crossterm::queue!(
+                stdout,
+                Print("\n"),
+                MoveToColumn(0),
+                Print(" ".repeat(indent)),
+                Print(style(repeats).with(GUTTER_COLOR))
+            )?;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: None,
+            }));
+        }
+
+        // only the name is clickable, not the padding that aligns it
+        let padded = name.content();
+        let start = p + ts_width + (padded.len() - padded.trim_start().len());
+        let name_columns = start as u16..(start + padded.trim().width()) as u16;
+
+        for (i, part) in lines.into_iter().enumerate() {
             let first = i == 0;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: Some(name_columns.clone()).filter(|_| first),
+            }));
 
             crossterm::queue!(stdout, Print("\n"), MoveToColumn(0))?;
 
-            if let Some(prefix) = state.prefix {
-                if first {
-                    crossterm::queue!(
-                        stdout,
-                        Print("["),
-                        Print(style(prefix).with(Color::Yellow)),
-                        Print("] ")
-                    )?;
-                } else {
-                    crossterm::queue!(stdout, Print("    "))?;
-                }
-            }
-
             if first {
-                crossterm::queue!(stdout, Print(&name))?;
-            } else {
+                if let Some(prefix) = state.prefix {
+                    crossterm::queue!(stdout, Print("["))?;
+                    print_prefix(stdout, prefix, state.typed, state.picked)?;
+                    crossterm::queue!(stdout, Print("] "))?;
+                }
+                if let Some(ts) = &ts {
+                    crossterm::queue!(stdout, Print(style(ts).with(TS_COLOR)), Print(" "))?;
+                }
+                crossterm::queue!(stdout, Print(&name), Print(" "))?;
+            } else if state.continuation != Continuation::Full {
+                if state.prefix.is_some() {
+                    crossterm::queue!(stdout, Print(" ".repeat(p)))?;
+                }
                 crossterm::queue!(
                     stdout,
-                    Print(&state.pad[..state.pad.len() - p]),
-                    Print(state.indent)
+                    Print(" ".repeat(ts_width)),
+                    Print(&state.pad[..left - p]),
+                    Print(" ")
                 )?;
             }
-            crossterm::queue!(stdout, Print(" "), Print(part))?;
+
+            if let Some(separator) =
+                separator.filter(|_| first || state.continuation != Continuation::Full)
+            {
+                crossterm::queue!(stdout, Print(separator), Print(" "))?;
+            }
+
+            if let Some(gutter) = state.continuation.gutter(i == last).filter(|_| !first) {
+                crossterm::queue!(stdout, Print(gutter))?;
+            }
+            print_text(stdout, &part, linker.as_mut(), state.selected)?;
         }
 
-        Ok(())
+        Ok(rows)
     }
 
     fn print_compact(
@@ -243,56 +1366,778 @@ impl ViewMode {
         msg: &Message<'_>,
         state: State<'_>,
         color: Color,
-    ) -> anyhow::Result<()> {
-        const TS_FORMAT: usize = "HH:MM:SS".len();
+    ) -> anyhow::Result<Vec<Option<Row>>> {
+        let ts = state
+            .timestamp
+            .map(|timestamp| timestamp.render(&msg.ts, &state.now));
+        let ts = match (ts, state.show_latency) {
+            (Some(ts), true) => Some(format!("{} {}", ts, timestamp::latency(msg.latency))),
+            (None, true) => Some(timestamp::latency(msg.latency)),
+            (ts, false) => ts,
+        };
 
-        let name = msg.pm.name();
-        let middle = state
-            .show_timestamp
-            .then(|| state.width - name.width() - TS_FORMAT)
-            .unwrap_or_default();
+        // the timestamp shares the line with the name, with at least a space between them
+        let ts_width = ts.as_ref().map(|ts| ts.width() + 1).unwrap_or(0);
+        let available = state.width.saturating_sub(ts_width).max(1);
 
-        let name = (name.width() > state.width)
-            .then(|| truncate::truncate_or_pad(name, state.width))
+        let name = msg.pm.name();
+        let name = (name.width() > available)
+            .then(|| truncate::truncate_or_pad(name, available))
             .map(Cow::Owned)
             .unwrap_or_else(|| Cow::Borrowed(name));
 
+        let middle = ts
+            .as_ref()
+            .map(|ts| state.width.saturating_sub(name.width() + ts.width()))
+            .unwrap_or(0);
+
+        let mut rows = vec![];
+        if let Some(parent) = reply::Parent::new(&msg.pm) {
+            crossterm::queue!(
+                stdout,
+                Print("\n"),
+                MoveToColumn(0),
+                Print(style(parent.context(state.width)).with(GUTTER_COLOR))
+            )?;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: None,
+            }));
+        }
+        if let Some(repeats) = msg.repeats_line(state.width) {
+            crossterm::queue!(
+                stdout,
+                Print("\n"),
+                MoveToColumn(0),
+                Print(style(repeats).with(GUTTER_COLOR))
+            )?;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: None,
+            }));
+        }
+
         crossterm::queue!(
             stdout,
             Print("\n"),
             MoveToColumn(0),
-            Print(&style(name).with(color))
+            Print(&style(&name).with(color))
         )?;
+        let name = Some(0..name.width() as u16);
 
-        if state.show_timestamp {
-            let ts = style(msg.ts.format("%X").to_string()).with(TS_COLOR);
+        if let Some(ts) = ts {
+            let ts = style(ts).with(TS_COLOR);
             crossterm::queue!(stdout, Print(" ".repeat(middle)), Print(ts))?;
         }
 
-        crossterm::queue!(
-            stdout,
-            Print("\n"),
-            MoveToColumn(0),
-            Print(msg.pm.data()),
-            Print("\n"),
-            MoveToColumn(0)
-        )?;
+        let (data, width) = (msg.pm.data(), state.width);
+        let lines = match state.continuation {
+            Continuation::Hang => {
+                let rest = width.saturating_sub(GUTTER_WIDTH);
+                wrap::wrap_hanging(data, width, rest, state.whitespace)
+            }
+            Continuation::Align | Continuation::Full => wrap::wrap(data, width, state.whitespace),
+        };
+        let last = lines.len().saturating_sub(1);
 
-        Ok(())
+        let mut linker = Some(Linker::new(data)).filter(|_| state.links);
+        rows.push(Some(Row { id: msg.id, name }));
+
+        for (i, line) in lines.into_iter().enumerate() {
+            crossterm::queue!(stdout, Print("\n"), MoveToColumn(0))?;
+            if let Some(gutter) = state.continuation.gutter(i == last).filter(|_| i > 0) {
+                crossterm::queue!(stdout, Print(gutter))?;
+            }
+            print_text(stdout, &line, linker.as_mut(), state.selected)?;
+            rows.push(Some(Row {
+                id: msg.id,
+                name: None,
+            }));
+        }
+        crossterm::queue!(stdout, Print("\n"), MoveToColumn(0))?;
+        // the blank line between messages
+        rows.push(None);
+
+        Ok(rows)
     }
 }
 
 #[derive(Clone, Debug, PartialEq)]
-struct Message<'msg> {
-    pm: Privmsg<'msg>,
-    ts: chrono::DateTime<chrono::Local>,
+pub(crate) struct Message<'msg> {
+    pub(crate) id: usize,
+    pub(crate) pm: Privmsg<'msg>,
+    /// when it was sent, or when it was received if the server didn't say
+    pub(crate) ts: chrono::DateTime<chrono::Local>,
+    /// how long it took to get here, unknown for replayed messages
+    pub(crate) latency: Option<chrono::Duration>,
+    /// deleted, but it can still be brought back
+    pub(crate) hidden: bool,
+    /// how many times it was sent in a row, when those were collapsed into this
+    pub(crate) repeats: usize,
+    /// who sent it, in the order they first did. only set once it was repeated
+    pub(crate) senders: Vec<String>,
 }
 
 impl<'msg> Message<'msg> {
-    fn new(pm: Privmsg<'msg>) -> Self {
+    pub(crate) fn new(id: usize, pm: Privmsg<'msg>, ts: chrono::DateTime<chrono::Local>) -> Self {
         Self {
+            id,
             pm,
-            ts: chrono::Local::now(),
+            ts,
+            latency: None,
+            hidden: false,
+            repeats: 1,
+            senders: vec![],
+        }
+    }
+
+    fn repeated(&mut self, sender: String) {
+        if self.senders.is_empty() {
+            self.senders.push(self.pm.name().to_string());
+        }
+        if !self.senders.contains(&sender) {
+            self.senders.push(sender);
+        }
+        self.repeats += 1;
+    }
+
+    // `×3 museun, shaken_bot`, cut to fit in `width` columns
+    fn repeats_line(&self, width: usize) -> Option<String> {
+        if self.repeats < 2 {
+            return None;
+        }
+        let line = format!("×{} {}", self.repeats, self.senders.join(", "));
+        let line = truncate::truncate_or_pad(&line, width);
+        Some(line.trim_end().to_string())
+    }
+}
+
+// twitch refuses the same message twice in a row, so repeats often have an
+// invisible character added to them
+fn normalize(text: &str) -> String {
+    const INVISIBLE: &[char] = &[
+        '\u{e0000}',
+        '\u{34f}',
+        '\u{200b}',
+        '\u{200c}',
+        '\u{200d}',
+        '\u{2060}',
+        '\u{feff}',
+    ];
+    let text = text.replace(INVISIBLE, "");
+    let mut normalized = String::with_capacity(text.len());
+    for word in text.split_whitespace() {
+        if !normalized.is_empty() {
+            normalized.push(' ');
+        }
+        normalized.extend(word.chars().flat_map(char::to_lowercase));
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone as _;
+
+    use crate::{
+        terminal::Screen,
+        timestamp::{Style, Zone},
+        transcript,
+    };
+
+    const MESSAGES: &[(&str, &str)] = &[
+        ("museun", "hello world"),
+        (
+            "someone_with_a_long_name",
+            "this message is long enough that it has to be wrapped",
+        ),
+        ("shaken_bot", "!hello"),
+    ];
+
+    fn render(width: u16, height: u16, min_width: Option<usize>, mode: UpdateMode) -> String {
+        let args = Args {
+            min_width,
+            ..Args::default()
+        };
+        render_with(args, width, height, mode)
+    }
+
+    fn render_with(args: Args, width: u16, height: u16, mode: UpdateMode) -> String {
+        render_at(args, chrono::Local::now(), width, height, mode)
+    }
+
+    fn render_at(
+        args: Args,
+        ts: chrono::DateTime<chrono::Local>,
+        width: u16,
+        height: u16,
+        mode: UpdateMode,
+    ) -> String {
+        let mut window = Window::new(args.nick_max, args.buffer_max, args.min_width);
+        for (name, data) in MESSAGES {
+            let line = format!(
+                ":{name}!{name}@{name} PRIVMSG #testing :{data}",
+                name = name,
+                data = data
+            );
+            window.push_at(transcript::parse_privmsg(&line).unwrap(), ts);
+        }
+        draw(args, &mut window, width, height, mode)
+    }
+
+    fn draw(args: Args, window: &mut Window, width: u16, height: u16, mode: UpdateMode) -> String {
+        let screen = Screen::new(width, height);
+        let mut app = App::new(args, Window::new(0, 0, None), Box::new(screen.clone()));
+        window.update(&mut app, mode).unwrap();
+        screen.contents()
+    }
+
+    #[test]
+    fn bytes_per_redraw() {
+        let screen = Screen::new(80, 24);
+        let mut app = App::new(
+            Args::default(),
+            Window::new(0, 0, None),
+            Box::new(screen.clone()),
+        );
+        let mut window = Window::new(12, 100, None);
+        for i in 0..30 {
+            let line = format!(":museun!museun@museun PRIVMSG #testing :message {}", i);
+            window.push_at(
+                transcript::parse_privmsg(&line).unwrap(),
+                chrono::Local::now(),
+            );
+        }
+
+        let mut redraw = |window: &mut Window| {
+            let written = screen.written();
+            window.update(&mut app, UpdateMode::Redraw).unwrap();
+            screen.written() - written
+        };
+        let full = redraw(&mut window);
+        assert!(screen.contents().ends_with("museun       message 29"));
+
+        // nothing changed, so nothing is written
+        assert_eq!(redraw(&mut window), 0);
+
+        // selecting a message only changes its row
+        assert!(window.click(15, 10));
+        let selected = redraw(&mut window);
+        assert!(
+            selected > 0 && selected * 10 < full,
+            "{} of {}",
+            selected,
+            full
+        );
+    }
+
+    // run with `cargo test bench_redraw -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_redraw() {
+        // each case is measured with the frame invalidated first, like the full
+        // repaint before the frame kept track of the rows, and without
+        fn measure(invalidate: bool) -> [usize; 4] {
+            let screen = Screen::new(80, 24);
+            let mut app = App::new(
+                Args::default(),
+                Window::new(0, 0, None),
+                Box::new(screen.clone()),
+            );
+            let mut window = Window::new(12, 100, None);
+            for i in 0..30 {
+                let line = format!(":museun!museun@museun PRIVMSG #testing :message {}", i);
+                window.push_at(
+                    transcript::parse_privmsg(&line).unwrap(),
+                    chrono::Local::now(),
+                );
+            }
+
+            let mut redraw = |window: &mut Window| {
+                if invalidate {
+                    window.invalidate();
+                }
+                let written = screen.written();
+                window.update(&mut app, UpdateMode::Redraw).unwrap();
+                screen.written() - written
+            };
+            let full = redraw(&mut window);
+            let unchanged = redraw(&mut window);
+            window.click(15, 10);
+            let single_row = redraw(&mut window);
+            screen.resize(100, 30);
+            let resize = redraw(&mut window);
+            [full, unchanged, single_row, resize]
         }
+
+        let (before, after) = (measure(true), measure(false));
+        println!("{:<12} {:>8} {:>8}", "bytes", "before", "after");
+        for (i, case) in ["full", "unchanged", "single row", "resize"]
+            .iter()
+            .enumerate()
+        {
+            println!("{:<12} {:>8} {:>8}", case, before[i], after[i]);
+        }
+    }
+
+    #[test]
+    fn normal() {
+        let expected = [
+            "",
+            "museun      hello world",
+            "someone_wi… this message is long enough",
+            "            that it has to be wrapped",
+            "shaken_bot  !hello",
+        ];
+        assert_eq!(
+            render(40, 10, None, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn normal_narrow() {
+        // the name column gives way to the text, so everything stays on the screen
+        let expected = [
+            "",
+            "museun  hello",
+            "        world",
+            "someon… this",
+            "        message is",
+            "        long",
+            "        enough",
+            "        that it",
+            "        has to be",
+            "        wrapped",
+            "shaken… !hello",
+        ];
+        assert_eq!(
+            render(18, 12, Some(1), UpdateMode::Redraw),
+            expected.join("\n")
+        );
+
+        // and when even that doesn't leave room, it's shown compactly
+        let expected = [
+            "museun",
+            "hello",
+            "world",
+            "",
+            "someone_w…",
+            "this",
+            "message is",
+            "long",
+            "enough",
+            "that it",
+            "has to be",
+            "wrapped",
+            "",
+            "shaken_bot",
+            "!hello",
+        ];
+        assert_eq!(
+            render(10, 16, Some(1), UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let expected = [
+            "",
+            "museun",
+            "hello world",
+            "",
+            "someone_with_a_long…",
+            "this message is long",
+            "enough that it has",
+            "to be wrapped",
+            "",
+            "shaken_bot",
+            "!hello",
+        ];
+        assert_eq!(
+            render(20, 12, None, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn mark_all() {
+        let expected = [
+            "",
+            "[C] museun  hello world",
+            "[B] someon… this message is long enough",
+            "            that it has to be wrapped",
+            "[A] shaken… !hello",
+        ];
+        assert_eq!(
+            render(40, 10, None, UpdateMode::MarkAll),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn mark_all_wrapped() {
+        // the oldest message doesn't fit, because the one after it takes two rows
+        let expected = [
+            "[B] someon… this message is long enough",
+            "            that it has to be wrapped",
+            "[A] shaken… !hello",
+        ];
+        assert_eq!(
+            render(40, 3, None, UpdateMode::MarkAll),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn labels() {
+        assert_eq!(super::labels(3), ["A", "B", "C"]);
+        assert_eq!(super::labels(62).last().unwrap(), "z");
+
+        let labels = super::labels(100);
+        assert_eq!(labels[..3], ["AA", "AB", "AC"]);
+        assert_eq!(labels[62], "BA");
+        assert!(labels.iter().all(|label| label.len() == 2));
+    }
+
+    #[test]
+    fn scrolled_off() {
+        // the blank line at the top scrolls off first
+        let expected = [
+            "museun      hello world",
+            "someone_wi… this message is long enough",
+            "            that it has to be wrapped",
+            "shaken_bot  !hello",
+        ];
+        assert_eq!(render(40, 4, None, UpdateMode::Redraw), expected.join("\n"));
+    }
+
+    fn with_continuation(continuation: Continuation) -> Args {
+        Args {
+            continuation,
+            compact_continuation: continuation,
+            ..Args::default()
+        }
+    }
+
+    #[test]
+    fn continuation_hang() {
+        let args = with_continuation(Continuation::Hang);
+        let expected = [
+            "",
+            "[C] museun  hello world",
+            "[B] someon… this message is long",
+            "            │ enough that it has",
+            "            ↳ to be wrapped",
+            "[A] shaken… !hello",
+        ];
+        assert_eq!(
+            render_with(args, 34, 10, UpdateMode::MarkAll),
+            expected.join("\n")
+        );
+
+        let args = with_continuation(Continuation::Hang);
+        let expected = [
+            "",
+            "museun",
+            "hello world",
+            "",
+            "someone_with_a_long…",
+            "this message is long",
+            "│ enough that it has",
+            "↳ to be wrapped",
+            "",
+            "shaken_bot",
+            "!hello",
+        ];
+        assert_eq!(
+            render_with(args, 20, 12, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn continuation_full() {
+        let args = with_continuation(Continuation::Full);
+        let expected = [
+            "",
+            "museun      hello world",
+            "someone_wi… this message is long",
+            "enough that it has to be wrapped",
+            "shaken_bot  !hello",
+        ];
+        assert_eq!(
+            render_with(args, 34, 10, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn nick_column() {
+        let args = Args {
+            nick_align: Align::Right,
+            nick_ellipsis: Ellipsis::Middle,
+            nick_separator: Some("│".to_string()),
+            ..Args::default()
+        };
+        let expected = [
+            "",
+            "     museun │ hello world",
+            "someo…_name │ this message is long",
+            "            │ enough that it has",
+            "            │ to be wrapped",
+            " shaken_bot │ !hello",
+        ];
+        assert_eq!(
+            render_with(args, 34, 10, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+
+        let args = Args {
+            nick_align: Align::Center,
+            nick_separator: Some("│".to_string()),
+            continuation: Continuation::Hang,
+            ..Args::default()
+        };
+        let expected = [
+            "",
+            "[C] museun  │ hello world",
+            "[B] someon… │ this message is long",
+            "            │ │ enough that it has",
+            "            │ ↳ to be wrapped",
+            "[A] shaken… │ !hello",
+        ];
+        assert_eq!(
+            render_with(args, 34, 10, UpdateMode::MarkAll),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn timestamps() {
+        let args = Args {
+            timestamps: true,
+            timestamp: Timestamp {
+                style: Style::Relative,
+                zone: Zone::Local,
+            },
+            ..Args::default()
+        };
+        let expected = [
+            "",
+            "[C]     now museun  hello world",
+            "[B]     now someon… this message is long",
+            "                    enough that it has",
+            "                    to be wrapped",
+            "[A]     now shaken… !hello",
+        ];
+        assert_eq!(
+            render_with(args, 40, 10, UpdateMode::MarkAll),
+            expected.join("\n")
+        );
+
+        // 2021-04-05 13:14:15 UTC
+        let ts = chrono::Local.timestamp_opt(1_617_628_455, 0).unwrap();
+        let args = Args {
+            timestamps: true,
+            timestamp: Timestamp {
+                style: "%H:%M".parse().unwrap(),
+                zone: Zone::Utc,
+            },
+            continuation: Continuation::Full,
+            ..Args::default()
+        };
+        let expected = [
+            "",
+            "13:14 museun      hello world",
+            "13:14 someone_wi… this message is long",
+            "enough that it has to be wrapped",
+            "13:14 shaken_bot  !hello",
+        ];
+        assert_eq!(
+            render_at(args, ts, 40, 10, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+
+        let args = Args {
+            timestamps: true,
+            timestamp: Timestamp {
+                style: "%H:%M".parse().unwrap(),
+                zone: Zone::Utc,
+            },
+            ..Args::default()
+        };
+        let expected = [
+            "",
+            "museun         13:14",
+            "hello world",
+            "",
+            "someone_with_… 13:14",
+            "this message is long",
+            "enough that it has",
+            "to be wrapped",
+            "",
+            "shaken_bot     13:14",
+            "!hello",
+        ];
+        assert_eq!(
+            render_at(args, ts, 20, 12, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+    }
+
+    #[test]
+    fn sent_ts() {
+        let args = Args {
+            timestamps: true,
+            timestamp: Timestamp {
+                style: "%H:%M:%S".parse().unwrap(),
+                zone: Zone::Utc,
+            },
+            latency: true,
+            ..Args::default()
+        };
+
+        // 2021-04-05 13:14:15 UTC
+        let sent = chrono::Local.timestamp_opt(1_617_628_455, 0).unwrap();
+
+        let mut window = Window::new(args.nick_max, args.buffer_max, args.min_width);
+        let with_ts = format!(
+            "@tmi-sent-ts={} :museun!museun@museun PRIVMSG #testing :hello",
+            sent.timestamp_millis()
+        );
+        let received = sent + chrono::Duration::milliseconds(120);
+        window.push_received(transcript::parse_privmsg(&with_ts).unwrap(), received);
+
+        // without the tag, it was sent when it was received
+        let received = sent + chrono::Duration::seconds(30);
+        let without = ":shaken_bot!shaken_bot@shaken_bot PRIVMSG #testing :world";
+        window.push_received(transcript::parse_privmsg(without).unwrap(), received);
+
+        // and replayed messages have no latency
+        window.push_at(transcript::parse_privmsg(without).unwrap(), sent);
+
+        let expected = [
+            "",
+            "13:14:15 +120ms museun      hello",
+            "13:14:45        shaken_bot  world",
+            "13:14:15        shaken_bot  world",
+        ];
+        assert_eq!(
+            draw(args, &mut window, 40, 6, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+
+        // the compact view puts them in the same order
+        let args = Args {
+            timestamps: true,
+            timestamp: Timestamp {
+                style: "%H:%M:%S".parse().unwrap(),
+                zone: Zone::Utc,
+            },
+            latency: true,
+            min_width: Some(100),
+            ..Args::default()
+        };
+        let mut window = Window::new(args.nick_max, args.buffer_max, args.min_width);
+        let received = sent + chrono::Duration::milliseconds(120);
+        window.push_received(transcript::parse_privmsg(&with_ts).unwrap(), received);
+        assert_eq!(
+            draw(args, &mut window, 30, 3, UpdateMode::Redraw),
+            "museun         13:14:15 +120ms\nhello"
+        );
+    }
+
+    #[test]
+    fn collapse() {
+        let mut window = Window::new(11, 100, None).with_collapse(Some(3));
+        let messages = [
+            ("museun", "hello world"),
+            ("shaken_bot", "Hello  world"),
+            ("someone", "not the same"),
+            ("museun", "spam"),
+            ("shaken_bot", "spam"),
+            ("museun", "SPAM \u{e0000}"),
+            ("someone", "spam"),
+        ];
+        for (name, data) in messages.iter() {
+            let line = format!(
+                ":{name}!{name}@{name} PRIVMSG #testing :{data}",
+                name = name,
+                data = data
+            );
+            window.push_at(
+                transcript::parse_privmsg(&line).unwrap(),
+                chrono::Local::now(),
+            );
+        }
+
+        // the run has to be long enough, and be uninterrupted
+        let expected = [
+            "",
+            "museun      hello world",
+            "shaken_bot  Hello world",
+            "someone     not the same",
+            "            ×4 museun, shaken_bot, someone",
+            "museun      spam",
+        ];
+        assert_eq!(
+            draw(Args::default(), &mut window, 50, 8, UpdateMode::Redraw),
+            expected.join("\n")
+        );
+        assert_eq!(window.queue.len(), 4);
+    }
+
+    #[test]
+    fn collapse_scrolled() {
+        let mut window = Window::new(11, 100, None).with_collapse(Some(3));
+        let push = |window: &mut Window, name: &str, data: &str| {
+            let line = format!(":{0}!{0}@{0} PRIVMSG #testing :{1}", name, data);
+            window.push_at(
+                transcript::parse_privmsg(&line).unwrap(),
+                chrono::Local::now(),
+            );
+        };
+        for data in ["one", "two", "three", "spam", "spam"] {
+            push(&mut window, "museun", data);
+        }
+        assert!(window.scroll_up(3));
+
+        // the message it was scrolled back to stays at the bottom
+        push(&mut window, "someone", "spam");
+        assert_eq!(window.queue.len(), 4);
+        assert_eq!(window.scroll, 2);
+    }
+
+    #[test]
+    fn links() {
+        let args = Args {
+            links: true,
+            ..Args::default()
+        };
+        let window = Window::new(args.nick_max, args.buffer_max, args.min_width);
+        let line = ":museun!museun@museun PRIVMSG #testing :see https://example.com/a/long/path ok";
+        let msg = Message::new(
+            0,
+            transcript::parse_privmsg(line).unwrap(),
+            chrono::Local::now(),
+        );
+
+        let mut out = vec![];
+        let rows = ViewMode::Normal
+            .print_message(&mut out, &msg, window.state(30, &args, ViewMode::Normal))
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        // the url is broken over two lines, both parts link to all of it
+        let url = "https://example.com/a/long/path";
+        assert!(out.contains(&link::hyperlink("https://example.co", url)));
+        assert!(out.contains(&link::hyperlink("m/a/long/path", url)));
+        assert!(out.ends_with(" ok"));
+
+        let row = |name| Some(Row { id: 0, name });
+        assert_eq!(rows, [row(Some(0..6)), row(None), row(None)]);
     }
 }
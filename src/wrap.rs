@@ -0,0 +1,299 @@
+use unicode_segmentation::UnicodeSegmentation as _;
+use unicode_width::UnicodeWidthStr as _;
+
+/// How runs of whitespace are treated when wrapping
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Whitespace {
+    /// Runs are collapsed to a single space, and dropped where a line breaks
+    Collapse,
+    /// Runs are kept as is, so the lines concatenate back to the input
+    ///
+    /// Except for tabs, which are expanded to [`TAB`] so they have a width.
+    Preserve,
+}
+
+/// What a tab is expanded to, a terminal would move it to wherever the next tab stop is
+pub const TAB: &str = "    ";
+
+/// Wraps `input` into lines that are at most `max` columns wide
+///
+/// Lines are broken between words. Words that are wider than a line (e.g. long
+/// urls) are broken between grapheme clusters, without a hyphen. A single
+/// grapheme wider than `max` gets a line to itself.
+pub fn wrap(input: &str, max: usize, whitespace: Whitespace) -> Vec<String> {
+    wrap_hanging(input, max, max, whitespace)
+}
+
+/// Like [`wrap`], but the lines after the first are at most `rest` columns wide
+pub fn wrap_hanging(input: &str, first: usize, rest: usize, whitespace: Whitespace) -> Vec<String> {
+    let mut wrapper = Wrapper {
+        first: first.max(1),
+        rest: rest.max(1),
+        lines: vec![],
+        line: String::new(),
+        width: 0,
+        space: false,
+    };
+
+    for (is_space, token) in tokens(input) {
+        match (is_space, whitespace) {
+            (true, Whitespace::Collapse) => wrapper.space = true,
+            (true, Whitespace::Preserve) => wrapper.push_broken(&token.replace('\t', TAB)),
+            (false, ..) => wrapper.push_word(token),
+        }
+    }
+
+    if !wrapper.line.is_empty() {
+        wrapper.lines.push(wrapper.line)
+    }
+    wrapper.lines
+}
+
+struct Wrapper {
+    first: usize,
+    rest: usize,
+    lines: Vec<String>,
+    line: String,
+    width: usize,
+    // a collapsed space is waiting for the next word
+    space: bool,
+}
+
+impl Wrapper {
+    fn max(&self) -> usize {
+        match self.lines.is_empty() {
+            true => self.first,
+            false => self.rest,
+        }
+    }
+
+    fn push_word(&mut self, word: &str) {
+        let space = std::mem::take(&mut self.space) && !self.line.is_empty();
+        let width = word.width() + space as usize;
+
+        if self.width + width <= self.max() {
+            if space {
+                self.push(" ", 1);
+            }
+            self.push(word, width - space as usize);
+            return;
+        }
+
+        if !self.line.is_empty() {
+            self.break_line();
+        }
+
+        let width = word.width();
+        if width <= self.max() {
+            self.push(word, width);
+            return;
+        }
+
+        self.push_broken(word)
+    }
+
+    // this breaks wherever it needs to, rather than between words
+    fn push_broken(&mut self, text: &str) {
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width();
+            if self.width + width > self.max() && !self.line.is_empty() {
+                self.break_line();
+            }
+            self.push(grapheme, width);
+        }
+    }
+
+    fn push(&mut self, text: &str, width: usize) {
+        self.line.push_str(text);
+        self.width += width;
+    }
+
+    fn break_line(&mut self) {
+        self.lines.push(std::mem::take(&mut self.line));
+        self.width = 0;
+    }
+}
+
+// runs of whitespace and runs of everything else, split on grapheme boundaries
+fn tokens(input: &str) -> impl Iterator<Item = (bool, &str)> {
+    let mut graphemes = input.grapheme_indices(true).peekable();
+    std::iter::from_fn(move || {
+        let (start, first) = graphemes.next()?;
+        let is_space = is_whitespace(first);
+
+        let mut end = start + first.len();
+        while let Some((i, grapheme)) = graphemes.next_if(|(_, g)| is_whitespace(g) == is_space) {
+            end = i + grapheme.len();
+        }
+
+        Some((is_space, &input[start..end]))
+    })
+}
+
+fn is_whitespace(grapheme: &str) -> bool {
+    grapheme.chars().all(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap() {
+        let input = "hello       world !this !is, !some, !commands. And this is a new statement?";
+
+        let expected = [
+            "hello world !this",
+            "!is, !some,",
+            "!commands. And this",
+            "is a new statement?",
+        ];
+        assert_eq!(super::wrap(input, 20, Whitespace::Collapse), expected);
+
+        let expected = [
+            "hello       world ",
+            "!this !is, !some, ",
+            "!commands. And this ",
+            "is a new statement?",
+        ];
+        assert_eq!(super::wrap(input, 20, Whitespace::Preserve), expected);
+    }
+
+    #[test]
+    fn hard_break() {
+        let input = "see https://example.com/some/long/path ok";
+        let expected = ["see", "https://ex", "ample.com/", "some/long/", "path ok"];
+        assert_eq!(super::wrap(input, 10, Whitespace::Collapse), expected);
+    }
+
+    #[test]
+    fn graphemes() {
+        // wide characters are measured by their display width
+        let expected = ["日本語", "日本語"];
+        assert_eq!(
+            super::wrap("日本語日本語", 7, Whitespace::Collapse),
+            expected
+        );
+
+        // combining accents and zwj sequences aren't split, even when they can't fit
+        let family = "👨\u{200d}👩\u{200d}👧";
+        let input = format!("e\u{301}e\u{301}e\u{301}{}{}", family, family);
+        let expected = ["e\u{301}e\u{301}e\u{301}", family, family];
+        assert_eq!(super::wrap(&input, 3, Whitespace::Collapse), expected);
+
+        // a grapheme that can never fit gets its own line
+        assert_eq!(
+            super::wrap("a日b", 1, Whitespace::Collapse),
+            ["a", "日", "b"]
+        );
+    }
+
+    #[test]
+    fn hanging() {
+        let input = "the first line is longer than the rest";
+        let expected = ["the first line is", "longer", "than the", "rest"];
+        assert_eq!(
+            super::wrap_hanging(input, 20, 8, Whitespace::Collapse),
+            expected
+        );
+    }
+
+    #[test]
+    fn empty() {
+        assert!(super::wrap("", 10, Whitespace::Collapse).is_empty());
+        assert!(super::wrap("   ", 10, Whitespace::Collapse).is_empty());
+        assert_eq!(super::wrap("   ", 2, Whitespace::Preserve), ["  ", " "]);
+    }
+
+    #[test]
+    fn tabs() {
+        // they're as wide as they are when expanded, rather than zero columns
+        assert_eq!(
+            super::wrap("a\tb\tc", 6, Whitespace::Preserve),
+            ["a    b", "    c"]
+        );
+        assert_eq!(super::wrap("a\tb", 6, Whitespace::Collapse), ["a b"]);
+    }
+
+    const PIECES: &[&str] = &[
+        "a",
+        "hello",
+        "world",
+        " ",
+        "   ",
+        "\t",
+        "日本語",
+        "e\u{301}",
+        "👨\u{200d}👩\u{200d}👧",
+        "🇯🇵",
+        "https://example.com/a/very/long/url",
+        "ok,",
+        "!cmd",
+        "مرحبا",
+        "x",
+    ];
+
+    fn random_input(rng: &fastrand::Rng) -> String {
+        (0..rng.usize(0..30))
+            .map(|_| PIECES[rng.usize(..PIECES.len())])
+            .collect()
+    }
+
+    // a line can only be too wide if it is a single grapheme that can never fit
+    fn assert_fits(input: &str, lines: &[String], max: usize) {
+        for line in lines {
+            assert!(
+                line.width() <= max || line.graphemes(true).count() == 1,
+                "{:?} is wider than {} (input: {:?})",
+                line,
+                max,
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn preserve_properties() {
+        let rng = fastrand::Rng::with_seed(0x1234);
+        for _ in 0..2000 {
+            let input = random_input(&rng);
+            let max = rng.usize(1..40);
+
+            let lines = super::wrap(&input, max, Whitespace::Preserve);
+            assert_fits(&input, &lines, max);
+            assert_eq!(lines.concat(), input.replace('\t', TAB), "max: {}", max);
+
+            let (first, rest) = (rng.usize(1..40), rng.usize(1..40));
+            let lines = super::wrap_hanging(&input, first, rest, Whitespace::Preserve);
+            assert_fits(&input, &lines[..lines.len().min(1)], first);
+            assert_fits(&input, lines.get(1..).unwrap_or_default(), rest);
+            assert_eq!(
+                lines.concat(),
+                input.replace('\t', TAB),
+                "first: {}, rest: {}",
+                first,
+                rest
+            );
+        }
+    }
+
+    #[test]
+    fn collapse_properties() {
+        let rng = fastrand::Rng::with_seed(0x5678);
+        for _ in 0..2000 {
+            let input = random_input(&rng);
+            let max = rng.usize(1..40);
+
+            let lines = super::wrap(&input, max, Whitespace::Collapse);
+            assert_fits(&input, &lines, max);
+
+            // only whitespace is lost, and never inside of a line
+            let strip = |s: &str| s.split_whitespace().collect::<String>();
+            assert_eq!(strip(&lines.concat()), strip(&input), "max: {}", max);
+            for line in &lines {
+                assert!(!line.is_empty() && line.trim() == line, "{:?}", line);
+                assert!(!line.contains("  "), "{:?}", line);
+            }
+        }
+    }
+}